@@ -0,0 +1,207 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+};
+
+use num::Integer;
+
+/// The states of a simulation start looping after `prefix` steps, every `period` steps.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) struct Cycle {
+    pub(crate) prefix: usize,
+    pub(crate) period: usize,
+}
+
+impl Cycle {
+    /// Smallest number of steps leading to the same state as `n` steps.
+    pub(crate) fn reduce(&self, n: usize) -> usize {
+        if n < self.prefix {
+            n
+        } else {
+            self.prefix + (n - self.prefix) % self.period
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Strategy {
+    /// Brent's algorithm, only keeps two states alive.
+    Brent,
+    /// Remembers a fingerprint of every state seen, confirming a match before trusting it.
+    Hash,
+}
+
+fn fingerprint<S: Hash>(state: &S) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    state.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn advance<S, F>(start: &S, step: &mut F, n: usize) -> S
+where
+    S: Clone,
+    F: FnMut(&S) -> S,
+{
+    (0..n).fold(start.clone(), |state, _| step(&state))
+}
+
+/// Both finders also return a state inside the loop, with the number of steps
+/// leading to it, so that reaching a later state needs less than a period.
+fn find_cycle_brent<S, F>(start: &S, mut step: F) -> (Cycle, S, usize)
+where
+    S: Clone + Eq,
+    F: FnMut(&S) -> S,
+{
+    let mut power = 1;
+    let mut period = 1;
+    let mut tortoise = start.clone();
+    let mut hare = step(start);
+    while tortoise != hare {
+        if power == period {
+            tortoise = hare.clone();
+            power *= 2;
+            period = 0;
+        }
+        hare = step(&hare);
+        period += 1;
+    }
+
+    let mut prefix = 0;
+    let mut tortoise = start.clone();
+    let mut hare = advance(start, &mut step, period);
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        prefix += 1;
+    }
+    (Cycle { prefix, period }, tortoise, prefix)
+}
+
+fn find_cycle_hash<S, F>(start: &S, mut step: F) -> (Cycle, S, usize)
+where
+    S: Clone + Eq + Hash,
+    F: FnMut(&S) -> S,
+{
+    let mut seen: HashMap<u64, Vec<usize>> = HashMap::new();
+    let mut state = start.clone();
+    let mut idx = 0;
+    loop {
+        let candidates = seen.entry(fingerprint(&state)).or_default();
+        for past in candidates.iter() {
+            if advance(start, &mut step, *past) == state {
+                let cycle = Cycle {
+                    prefix: *past,
+                    period: idx - past,
+                };
+                return (cycle, state, idx);
+            }
+        }
+        candidates.push(idx);
+        state = step(&state);
+        idx += 1;
+    }
+}
+
+/// Detects when repeatedly applying `step` from `start` loops back on an already seen state.
+pub(crate) fn find_cycle<S, F>(start: &S, step: F) -> Cycle
+where
+    S: Clone + Eq + Hash,
+    F: FnMut(&S) -> S,
+{
+    find_cycle_with(Strategy::Hash, start, step)
+}
+
+pub(crate) fn find_cycle_with<S, F>(strategy: Strategy, start: &S, step: F) -> Cycle
+where
+    S: Clone + Eq + Hash,
+    F: FnMut(&S) -> S,
+{
+    find_cycle_state(strategy, start, step).0
+}
+
+fn find_cycle_state<S, F>(strategy: Strategy, start: &S, step: F) -> (Cycle, S, usize)
+where
+    S: Clone + Eq + Hash,
+    F: FnMut(&S) -> S,
+{
+    match strategy {
+        Strategy::Brent => find_cycle_brent(start, step),
+        Strategy::Hash => find_cycle_hash(start, step),
+    }
+}
+
+/// State reached after `n` steps, without simulating more than one loop.
+pub(crate) fn state_at<S, F>(strategy: Strategy, start: &S, mut step: F, n: usize) -> S
+where
+    S: Clone + Eq + Hash,
+    F: FnMut(&S) -> S,
+{
+    let (cycle, state, idx) = find_cycle_state(strategy, start, &mut step);
+    if n < cycle.prefix {
+        return advance(start, &mut step, n);
+    }
+    let behind = (cycle.reduce(n) + cycle.period - cycle.reduce(idx)) % cycle.period;
+    advance(&state, &mut step, behind)
+}
+
+/// Smallest `t` with `t = r1 mod m1` and `t = r2 mod m2`, through the chinese
+/// remainder theorem. Returned modulo the lcm of both moduli, along with it.
+pub(crate) fn merge_congruences(
+    (r1, m1): (usize, usize),
+    (r2, m2): (usize, usize),
+) -> Option<(usize, usize)> {
+    let (r1, m1, r2, m2) = (r1 as i128, m1 as i128, r2 as i128, m2 as i128);
+    let gcd = m1.extended_gcd(&m2);
+    if (r2 - r1) % gcd.gcd != 0 {
+        return None;
+    }
+    let lcm = m1 / gcd.gcd * m2;
+    let k = ((r2 - r1) / gcd.gcd * gcd.x).rem_euclid(m2 / gcd.gcd);
+    Some(((r1 + m1 * k).rem_euclid(lcm) as usize, lcm as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_cycle, find_cycle_with, merge_congruences, state_at, Cycle, Strategy};
+
+    fn step(v: &u64) -> u64 {
+        (v * v + 1) % 255
+    }
+
+    #[test]
+    fn test_0() {
+        let expected = Cycle {
+            prefix: 2,
+            period: 6,
+        };
+        assert_eq!(expected, find_cycle(&3, step));
+        assert_eq!(expected, find_cycle_with(Strategy::Brent, &3, step));
+    }
+    #[test]
+    fn test_1() {
+        let naive = |n: usize| (0..n).fold(3, |v, _| step(&v));
+        [0, 1, 2, 7, 8, 100, 1_001].into_iter().for_each(|n| {
+            assert_eq!(naive(n), state_at(Strategy::Hash, &3, step, n));
+            assert_eq!(naive(n), state_at(Strategy::Brent, &3, step, n));
+        });
+    }
+    #[test]
+    fn test_2() {
+        let cycle = find_cycle(&0, |v: &u8| (v + 1) % 5);
+        assert_eq!(
+            Cycle {
+                prefix: 0,
+                period: 5
+            },
+            cycle
+        );
+        assert_eq!(4, cycle.reduce(1_000_000_004));
+    }
+    #[test]
+    fn test_3() {
+        assert_eq!(Some((11, 12)), merge_congruences((3, 4), (5, 6)));
+        assert_eq!(Some((3, 5)), merge_congruences((0, 1), (3, 5)));
+        assert_eq!(Some((17, 105)), merge_congruences((2, 15), (3, 7)));
+        assert_eq!(None, merge_congruences((1, 2), (2, 4)));
+    }
+}
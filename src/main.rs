@@ -1,3 +1,4 @@
+mod cycle;
 mod year2023;
 
 use std::fs;
@@ -21,6 +22,105 @@ struct Args {
     /// path to the file
     #[arg(short, long)]
     pub filename: String,
+
+    /// Expansion factors of the empty space, at least 1 (day 11)
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_values_t = [2, 1_000_000],
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub factors: Vec<usize>,
+
+    /// Number of copies when unfolding the records (day 12)
+    #[arg(long, default_value_t = 5)]
+    pub unfold: usize,
+
+    /// Seed of the random sampling (day 12)
+    #[arg(long, default_value_t = 42)]
+    pub seed: u64,
+
+    /// Only print the k-th arrangement in lexicographic order (day 12)
+    #[arg(long)]
+    pub kth: Option<num::BigUint>,
+
+    /// Tilt program, e.g. `NWSE*1000000000` (day 14)
+    #[arg(long, default_value = "NWSE*1000000000")]
+    pub program: String,
+
+    /// Edge on which the load is measured (day 14)
+    #[arg(long, default_value_t = 'N')]
+    pub edge: char,
+
+    /// Print the intermediate states
+    #[arg(long)]
+    pub trace: bool,
+
+    /// Multiplier of the HASH algorithm (day 15)
+    #[arg(long, default_value_t = 17)]
+    pub multiplier: u64,
+
+    /// Modulus of the HASH algorithm, at least 1 (day 15)
+    #[arg(long, default_value_t = 256, value_parser = clap::value_parser!(u64).range(1..))]
+    pub modulus: u64,
+
+    /// Starting value of the HASH algorithm (day 15)
+    #[arg(long, default_value_t = 0)]
+    pub hash_seed: u64,
+
+    /// Delay in milliseconds between two animation frames, final state only if absent
+    #[arg(long)]
+    pub delay: Option<u64>,
+
+    /// Moves to do in a direction before turning or stopping (day 17)
+    #[arg(long, default_value_t = 1)]
+    pub min_run: usize,
+
+    /// Moves allowed in a direction before having to turn (day 17)
+    #[arg(long, default_value_t = 3)]
+    pub max_run: usize,
+
+    /// Starting block as `row,col` (day 17)
+    #[arg(long, value_parser = parse_block, default_value = "0,0")]
+    pub start: (usize, usize),
+
+    /// Goal block as `row,col`, bottom-right block if absent (day 17)
+    #[arg(long, value_parser = parse_block)]
+    pub goal: Option<(usize, usize)>,
+
+    /// Forbid the crucible to turn left (day 17)
+    #[arg(long)]
+    pub no_left: bool,
+
+    /// Forbid the crucible to turn right (day 17)
+    #[arg(long)]
+    pub no_right: bool,
+
+    /// Allow the crucible to turn back (day 17)
+    #[arg(long)]
+    pub u_turns: bool,
+
+    /// Use Dijkstra instead of A* (day 17)
+    #[arg(long)]
+    pub dijkstra: bool,
+
+    /// Explain how every part went through the workflows (day 19)
+    #[arg(long)]
+    pub explain: bool,
+}
+
+/// `row,col` with exactly two coordinates.
+fn parse_block(value: &str) -> Result<(usize, usize), String> {
+    let (row, col) = value
+        .split_once(',')
+        .ok_or_else(|| format!("expected `row,col`, got `{value}`"))?;
+    let parse = |coord: &str| {
+        coord
+            .trim()
+            .parse::<usize>()
+            .map_err(|_| format!("invalid coordinate `{coord}` in `{value}`"))
+    };
+    Ok((parse(row)?, parse(col)?))
 }
 
 fn main() {
@@ -49,24 +149,83 @@ fn main() {
         (2023, 9, 1) => year2023::day09::print_sol_2(content),
         (2023, 10, 0) => year2023::day10::print_sol_1(content),
         (2023, 10, 1) => year2023::day10::print_sol_2(content),
+        (2023, 10, 2) => year2023::day10::print_render(content),
         (2023, 11, 0) => year2023::day11::print_sol_1(content),
         (2023, 11, 1) => year2023::day11::print_sol_2(content),
+        (2023, 11, 2) => year2023::day11::print_factors(content, &args.factors),
+        (2023, 11, 3) => year2023::day11::print_pairs(content, &args.factors),
         (2023, 12, 0) => year2023::day12::print_sol_1(content),
         (2023, 12, 1) => year2023::day12::print_sol_2(content),
+        (2023, 12, 2) => year2023::day12::print_unfolded(content, args.unfold),
+        (2023, 12, 3) => year2023::day12::print_arrangements(content, args.kth),
+        (2023, 12, 4) => year2023::day12::print_samples(content, args.unfold, args.seed),
         (2023, 13, 0) => year2023::day13::print_sol_1(content),
         (2023, 13, 1) => year2023::day13::print_sol_2(content),
         (2023, 14, 0) => year2023::day14::print_sol_1(content),
         (2023, 14, 1) => year2023::day14::print_sol_2(content),
+        (2023, 14, 2) => {
+            year2023::day14::print_program(content, &args.program, args.edge, args.trace)
+        }
         (2023, 15, 0) => year2023::day15::print_sol_1(content),
+        (2023, 15, 1) if args.trace => year2023::day15::print_trace(content),
         (2023, 15, 1) => year2023::day15::print_sol_2(content),
+        (2023, 15, 2) => year2023::day15::print_analysis(
+            content,
+            year2023::day15::LabelHasher {
+                multiplier: args.multiplier,
+                modulus: args.modulus,
+                seed: args.hash_seed,
+            },
+        ),
         (2023, 16, 0) => year2023::day16::print_sol_1(content),
         (2023, 16, 1) => year2023::day16::print_sol_2(content),
+        (2023, 16, 2) => year2023::day16::print_sol_2_simulated(content),
+        (2023, 16, 3) => year2023::day16::print_visualization(content, args.delay),
+        (2023, 16, 4) => year2023::day16::print_best_visualization(content, args.delay),
         (2023, 17, 0) => year2023::day17::print_sol_1(content),
         (2023, 17, 1) => year2023::day17::print_sol_2(content),
+        (2023, 17, 2) => year2023::day17::print_path_1(content),
+        (2023, 17, 3) => year2023::day17::print_path_2(content),
+        (2023, 17, 4) => year2023::day17::print_custom_path(
+            content,
+            &year2023::day17::Constraints {
+                min_run: args.min_run,
+                max_run: args.max_run,
+                start: args.start,
+                goal: args.goal,
+                turns: year2023::day17::Turns {
+                    left: !args.no_left,
+                    right: !args.no_right,
+                    u_turn: args.u_turns,
+                },
+                search: if args.dijkstra {
+                    year2023::day17::Search::Dijkstra
+                } else {
+                    year2023::day17::Search::AStar
+                },
+            },
+        ),
         (2023, 18, 0) => year2023::day18::print_sol_1(content),
         (2023, 18, 1) => year2023::day18::print_sol_2(content),
+        (2023, 18, 2) => year2023::day18::print_polygons(content),
+        (2023, 18, 3) => year2023::day18::print_svg(content, false),
+        (2023, 18, 4) => year2023::day18::print_svg(content, true),
+        (2023, 18, 5) => year2023::day18::print_ascii(content),
+        (2023, 19, 0) if args.explain => year2023::day19::print_explain(content),
         (2023, 19, 0) => year2023::day19::print_sol_1(content),
         (2023, 19, 1) => year2023::day19::print_sol_2(content),
+        (2023, 19, 2) => year2023::day19::print_analysis(content),
+        (2023, 19, 3) => year2023::day19::print_minimized(content),
+        (2023, 19, 4) => year2023::day19::print_regions(content, false),
+        (2023, 19, 5) => year2023::day19::print_regions(content, true),
+        (2023, 19, 6) => year2023::day19::print_dot(content),
+        (2023, 20, 0) => year2023::day20::print_sol_1(content),
+        (2023, 20, 1) => year2023::day20::print_sol_2(content),
+        (2023, 21, 0) => year2023::day21::print_sol_1(content),
+        (2023, 21, 1) => year2023::day21::print_sol_2(content),
+        (2023, 22, 0) => year2023::day22::print_sol_1(content),
+        (2023, 22, 1) => year2023::day22::print_sol_2(content),
+        (2023, 22, 2) => year2023::day22::print_dump(content),
         _ => unreachable!(),
     };
 }
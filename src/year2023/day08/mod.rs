@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use num::integer::lcm;
+use crate::cycle::{self, Cycle};
 
 #[derive(Debug)]
 struct Dest<'a> {
@@ -94,26 +94,34 @@ impl<'a> Solver<'a> {
         nbr_step + self.map.moves.len() as u32
     }
 
-    fn get_loop_len(self) -> Vec<usize> {
+    /// For every ghost, the loop it ends up in and the steps, among its first
+    /// `prefix + period` ones, at which it stands on a node ending with `Z`.
+    fn get_end_steps(self) -> Vec<(Cycle, Vec<usize>)> {
+        let moves = self.map.moves.chars().collect::<Vec<_>>();
+        let step = |(current_pos, move_idx): &(&'a str, usize)| {
+            let next_pos = self
+                .map
+                .map
+                .get(current_pos)
+                .expect("Location present")
+                .get_dest(Some(moves[*move_idx]));
+            (next_pos, (move_idx + 1) % moves.len())
+        };
         self.positions
-            .into_iter()
+            .iter()
             .map(|pos| {
-                let mut cycle = 0;
-                let mut moves = self.map.moves.chars().cycle();
-                let mut current_pos = pos;
-                while let Some(next_move) = moves.next() {
-                    current_pos = self
-                        .map
-                        .map
-                        .get(current_pos)
-                        .expect("Location present")
-                        .get_dest(Some(next_move));
-                    cycle += 1;
-                    if current_pos.ends_with('Z') {
-                        break;
+                // A ghost is only back where it was once both its node and its
+                // position in the list of moves repeat.
+                let cycle = cycle::find_cycle(&(*pos, 0), step);
+                let mut state = (*pos, 0);
+                let mut ends = vec![];
+                for nbr_step in 0..cycle.prefix + cycle.period {
+                    if state.0.ends_with('Z') {
+                        ends.push(nbr_step);
                     }
+                    state = step(&state);
                 }
-                cycle
+                (cycle, ends)
             })
             .collect()
         // eprintln!("pos : {:?}", self.positions);
@@ -174,10 +182,37 @@ pub(crate) fn eval_file(file: &str) -> u32 {
 pub(crate) fn eval_file_2(file: &str) -> usize {
     let map: Map = file.into();
     let solver = Solver::new(map, |v| v.ends_with('A'));
-    solver
-        .get_loop_len()
+    let ghosts = solver.get_end_steps();
+    let latest = ghosts.iter().map(|(c, _)| c.prefix).max().unwrap_or(0);
+    let all_on_end = |nbr_step: usize| {
+        ghosts
+            .iter()
+            .all(|(c, ends)| ends.contains(&c.reduce(nbr_step)))
+    };
+    if let Some(nbr_step) = (0..latest).find(|s| all_on_end(*s)) {
+        return nbr_step;
+    }
+    // Past every prefix, each ghost is on a `Z` node every `period` steps
+    // after each of the ones it meets within its loop.
+    ghosts
+        .iter()
+        .fold(vec![(0, 1)], |candidates, (c, ends)| {
+            candidates
+                .iter()
+                .flat_map(|candidate| {
+                    ends.iter()
+                        .filter(|e| **e >= c.prefix)
+                        .filter_map(|e| {
+                            cycle::merge_congruences(*candidate, (e % c.period, c.period))
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect()
+        })
         .into_iter()
-        .fold(1, |acc, v| lcm(acc, v))
+        .map(|(r, m)| r + (latest.saturating_sub(r)).div_ceil(m) * m)
+        .min()
+        .expect("Ghosts should all end on a Z node at the same time")
 }
 pub(crate) fn print_sol_1(file: &str) {
     print!("res : {}", eval_file(file));
@@ -225,8 +260,22 @@ XXX = (XXX, XXX)"#
         assert_eq!(2, eval_file(data()));
         assert_eq!(6, eval_file(data_1()));
     }
+    fn data_3() -> &'static str {
+        r#"L
+
+11A = (11B, XXX)
+11B = (11Z, XXX)
+11Z = (11C, XXX)
+11C = (11B, XXX)
+XXX = (XXX, XXX)"#
+    }
     #[test]
     fn test_1() {
         assert_eq!(6, eval_file_2(data_2()));
     }
+    #[test]
+    fn test_2() {
+        // The ghost loops over `11B`, `11Z` and `11C`, only ending on `11Z`.
+        assert_eq!(2, eval_file_2(data_3()));
+    }
 }
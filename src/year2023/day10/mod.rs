@@ -1,5 +1,9 @@
 use std::ops::{Deref, DerefMut};
 
+const LOOP_COLOR: &str = "\x1b[1;32m";
+const ENCLOSED_COLOR: &str = "\x1b[1;31m";
+const RESET_COLOR: &str = "\x1b[0m";
+
 struct Map(Vec<Vec<char>>);
 #[derive(PartialEq, Eq, Clone, Debug, Hash)]
 struct Coord((i32, i32));
@@ -115,45 +119,136 @@ fn explore_map(map: &Map, starting_pos: Coord) -> Vec<Coord> {
     unreachable!()
 }
 
-pub(crate) fn eval_file(file: &str) -> usize {
-    let map: Map = Map(file
-        .lines()
-        .filter(|l| !l.is_empty())
-        .map(|line| line.chars().collect())
-        .collect());
-    let s_pos = Coord(
-        map.iter()
+impl From<&str> for Map {
+    fn from(value: &str) -> Self {
+        Map(value
+            .lines()
+            .filter(|l| !l.is_empty())
+            .map(|line| line.chars().collect())
+            .collect())
+    }
+}
+
+impl Map {
+    fn starting_pos(&self) -> Coord {
+        Coord(
+            self.iter()
+                .enumerate()
+                .find_map(|(i, line)| {
+                    line.iter()
+                        .position(|c| c == &'S')
+                        .map(|pos| (i as i32, pos as i32))
+                })
+                .expect("Should have starting pos"),
+        )
+    }
+
+    /// Pipe hidden under `S`, deduced from its two neighbours on the loop.
+    fn start_pipe(&self, loop_coords: &[Coord]) -> char {
+        let start = &loop_coords[0];
+        let mut dirs = [&loop_coords[1], &loop_coords[loop_coords.len() - 1]]
+            .map(|c| (c.x() - start.x(), c.y() - start.y()));
+        dirs.sort();
+        match dirs {
+            [(-1, 0), (1, 0)] => '|',
+            [(0, -1), (0, 1)] => '-',
+            [(-1, 0), (0, 1)] => 'L',
+            [(-1, 0), (0, -1)] => 'J',
+            [(0, 1), (1, 0)] => 'F',
+            [(0, -1), (1, 0)] => '7',
+            _ => unreachable!(),
+        }
+    }
+
+    fn loop_tiles(&self, loop_coords: &[Coord]) -> Vec<Vec<bool>> {
+        let mut tiles = vec![vec![false; self[0].len()]; self.len()];
+        loop_coords
+            .iter()
+            .for_each(|c| tiles[c.x() as usize][c.y() as usize] = true);
+        tiles
+    }
+
+    /// Scanline enclosure : walking a row from the left, every loop tile
+    /// connected to the north toggles whether we are inside the loop.
+    fn enclosed_tiles(&self, loop_coords: &[Coord]) -> Vec<Vec<bool>> {
+        let start = &loop_coords[0];
+        let start_pipe = self.start_pipe(loop_coords);
+        let loop_tiles = self.loop_tiles(loop_coords);
+        self.iter()
             .enumerate()
-            .find_map(|(i, line)| {
-                if let Some(pos) = line.iter().position(|c| c == &'S') {
-                    Some((i as i32, pos as i32))
+            .map(|(x, line)| {
+                let mut inside = false;
+                line.iter()
+                    .enumerate()
+                    .map(|(y, c)| {
+                        if loop_tiles[x][y] {
+                            let c = if start.x() == x as i32 && start.y() == y as i32 {
+                                start_pipe
+                            } else {
+                                *c
+                            };
+                            if matches!(c, '|' | 'L' | 'J') {
+                                inside = !inside;
+                            }
+                            false
+                        } else {
+                            inside
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn render(&self) -> String {
+        let loop_coords = explore_map(self, self.starting_pos());
+        let start = &loop_coords[0];
+        let start_pipe = self.start_pipe(&loop_coords);
+        let loop_tiles = self.loop_tiles(&loop_coords);
+        let enclosed = self.enclosed_tiles(&loop_coords);
+        let mut ret = String::new();
+        self.iter().enumerate().for_each(|(x, line)| {
+            line.iter().enumerate().for_each(|(y, c)| {
+                let c = if start.x() == x as i32 && start.y() == y as i32 {
+                    start_pipe
+                } else {
+                    *c
+                };
+                let drawn = match c {
+                    '|' => '│',
+                    '-' => '─',
+                    'L' => '└',
+                    'J' => '┘',
+                    'F' => '┌',
+                    '7' => '┐',
+                    _ => ' ',
+                };
+                if loop_tiles[x][y] {
+                    ret.push_str(LOOP_COLOR);
+                    ret.push(drawn);
+                    ret.push_str(RESET_COLOR);
+                } else if enclosed[x][y] {
+                    ret.push_str(ENCLOSED_COLOR);
+                    ret.push('I');
+                    ret.push_str(RESET_COLOR);
                 } else {
-                    None
+                    ret.push(drawn);
                 }
-            })
-            .expect("Should have starting pos"),
-    );
-    explore_map(&map, s_pos).len() / 2
+            });
+            ret.push('\n');
+        });
+        ret
+    }
+}
+
+pub(crate) fn eval_file(file: &str) -> usize {
+    let map: Map = file.into();
+    explore_map(&map, map.starting_pos()).len() / 2
 }
 
 pub(crate) fn eval_file_2(file: &str) -> usize {
-    let map: Map = Map(file
-        .lines()
-        .filter(|l| !l.is_empty())
-        .map(|line| line.chars().collect())
-        .collect());
-    let s_pos = Coord(
-        map.iter()
-            .enumerate()
-            .find_map(|(i, line)| {
-                if let Some(pos) = line.iter().position(|c| c == &'S') {
-                    Some((i as i32, pos as i32))
-                } else {
-                    None
-                }
-            })
-            .expect("Should have starting pos"),
-    );
+    let map: Map = file.into();
+    let s_pos = map.starting_pos();
     let mut loop_coords = explore_map(&map, s_pos.clone());
     loop_coords.push(s_pos);
     let len_loop = loop_coords.len();
@@ -169,16 +264,35 @@ pub(crate) fn eval_file_2(file: &str) -> usize {
     area as usize - len_loop / 2 + 1
 }
 
+/// Same count as `eval_file_2`, computed with a scanline instead of Pick's theorem.
+pub(crate) fn eval_file_2_scanline(file: &str) -> usize {
+    let map: Map = file.into();
+    let loop_coords = explore_map(&map, map.starting_pos());
+    map.enclosed_tiles(&loop_coords)
+        .iter()
+        .map(|line| line.iter().filter(|inside| **inside).count())
+        .sum()
+}
+
 pub(crate) fn print_sol_1(file: &str) {
     print!("res : {}", eval_file(file));
 }
 pub(crate) fn print_sol_2(file: &str) {
     print!("res : {}", eval_file_2(file));
 }
+pub(crate) fn print_render(file: &str) {
+    let map: Map = file.into();
+    print!("{}", map.render());
+    print!(
+        "pick : {} // scanline : {}",
+        eval_file_2(file),
+        eval_file_2_scanline(file)
+    );
+}
 
 #[cfg(test)]
 mod tests {
-    use super::{eval_file, eval_file_2};
+    use super::{eval_file, eval_file_2, eval_file_2_scanline, Map};
 
     fn data_1() -> &'static str {
         r#"
@@ -251,4 +365,18 @@ L7JLJL-JLJLJL--JLJ.L"#
     fn test_3() {
         assert_eq!(10, eval_file_2(data_5()));
     }
+    #[test]
+    fn test_4() {
+        assert_eq!(4, eval_file_2_scanline(data_3()));
+        assert_eq!(8, eval_file_2_scanline(data_4()));
+        assert_eq!(10, eval_file_2_scanline(data_5()));
+    }
+    #[test]
+    fn test_5() {
+        let map: Map = data_3().into();
+        let rendered = map.render();
+        assert_eq!(4, rendered.matches('I').count());
+        assert!(rendered.contains('┌'));
+        assert!(!rendered.contains('S'));
+    }
 }
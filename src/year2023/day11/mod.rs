@@ -23,7 +23,7 @@ impl Deref for Map {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 struct Coord {
     x: usize,
     y: usize,
@@ -47,60 +47,91 @@ impl Map {
             })
             .collect::<Vec<_>>()
     }
-    fn get_empty_rows(&self) -> Vec<usize> {
+    fn get_empty_rows(&self) -> Vec<bool> {
         self.iter()
-            .enumerate()
-            .filter_map(|(i, line)| {
-                if line.iter().all(|c| c == &'.') {
-                    Some(i)
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>()
+            .map(|line| line.iter().all(|c| c == &'.'))
+            .collect()
     }
-    fn get_empty_cols(&self) -> Vec<usize> {
+    fn get_empty_cols(&self) -> Vec<bool> {
         (0..self[0].len())
-            .filter_map(|j| {
-                if (0..self.len()).all(|i| self[i][j] == '.') {
-                    Some(j)
-                } else {
-                    None
-                }
+            .map(|j| (0..self.len()).all(|i| self[i][j] == '.'))
+            .collect()
+    }
+
+    /// Galaxies coordinates once every empty row and column has been replaced
+    /// by `size_empty_galaxies` of them.
+    fn get_expanded_stars(&self, size_empty_galaxies: usize) -> Vec<Coord> {
+        let shift = |empty: Vec<bool>| {
+            empty
+                .into_iter()
+                .scan(0, |nbr_empty, is_empty| {
+                    let shifted = *nbr_empty * (size_empty_galaxies - 1);
+                    *nbr_empty += is_empty as usize;
+                    Some(shifted)
+                })
+                .collect::<Vec<_>>()
+        };
+        let rows_shift = shift(self.get_empty_rows());
+        let cols_shift = shift(self.get_empty_cols());
+        self.get_stars()
+            .into_iter()
+            .map(|Coord { x, y }| Coord {
+                x: x + rows_shift[x],
+                y: y + cols_shift[y],
             })
             .collect()
     }
 
     fn eval_min_distances(&self, size_empty_galaxies: usize) -> usize {
-        let stars_coord = self.get_stars();
+        let stars_coord = self.get_expanded_stars(size_empty_galaxies);
+        sum_pairwise_distances(stars_coord.iter().map(|c| c.x).collect())
+            + sum_pairwise_distances(stars_coord.iter().map(|c| c.y).collect())
+    }
 
-        let empty_rows = self.get_empty_rows();
-        let empty_cols = self.get_empty_cols();
+    fn eval_min_distances_multi(&self, sizes_empty_galaxies: &[usize]) -> Vec<usize> {
+        sizes_empty_galaxies
+            .iter()
+            .map(|size| self.eval_min_distances(*size))
+            .collect()
+    }
 
-        stars_coord
+    fn get_pair_distances(&self, size_empty_galaxies: usize) -> Vec<(Coord, Coord, usize)> {
+        self.get_expanded_stars(size_empty_galaxies)
             .into_iter()
             .tuple_combinations::<(Coord, Coord)>()
-            .fold(0, |acc, (c1, c2)| {
-                let max_x = c1.x.max(c2.x);
-                let min_x = c1.x.min(c2.x);
-                let max_y = c1.y.max(c2.y);
-                let min_y = c1.y.min(c2.y);
-                let dx = max_x - min_x;
-                let dy = max_y - min_y;
-                let dx_to_add = empty_rows
-                    .iter()
-                    .filter(|r| min_x <= **r && **r <= max_x)
-                    .count();
-                let dy_to_add = empty_cols
-                    .iter()
-                    .filter(|c| min_y <= **c && **c <= max_y)
-                    .count();
-                acc + dx + dy + (dx_to_add + dy_to_add) * (size_empty_galaxies - 1)
+            .map(|(c1, c2)| {
+                let dst = c1.x.abs_diff(c2.x) + c1.y.abs_diff(c2.y);
+                (c1, c2, dst)
             })
+            .collect()
+    }
+
+    /// The manhattan distance between two points is the biggest spread of
+    /// either `x + y` or `x - y`, so the farthest pair is found in one pass.
+    fn get_farthest_pair(&self, size_empty_galaxies: usize) -> Option<(Coord, Coord, usize)> {
+        let stars_coord = self.get_expanded_stars(size_empty_galaxies);
+        let spread = |key: &dyn Fn(&Coord) -> i64| {
+            let min = stars_coord.iter().min_by_key(|c| key(c))?;
+            let max = stars_coord.iter().max_by_key(|c| key(c))?;
+            Some((min.clone(), max.clone(), (key(max) - key(min)) as usize))
+        };
+        let sum = spread(&|c| (c.x + c.y) as i64)?;
+        let diff = spread(&|c| c.x as i64 - c.y as i64)?;
+        Some(if sum.2 >= diff.2 { sum } else { diff })
     }
 }
 
-// fn stars_coord(map: )
+/// Sum of `|a_i - a_j|` over every pair, using the sorted values and a running prefix sum.
+fn sum_pairwise_distances(mut values: Vec<usize>) -> usize {
+    values.sort_unstable();
+    values
+        .iter()
+        .enumerate()
+        .fold((0, 0), |(acc, prefix), (i, v)| {
+            (acc + v * i - prefix, prefix + v)
+        })
+        .0
+}
 
 pub(crate) fn eval_file(file: &str) -> usize {
     let map: Map = file.into();
@@ -117,10 +148,32 @@ pub(crate) fn print_sol_1(file: &str) {
 pub(crate) fn print_sol_2(file: &str) {
     print!("res : {}", eval_file_2(file));
 }
+pub(crate) fn print_factors(file: &str, sizes_empty_galaxies: &[usize]) {
+    let map: Map = file.into();
+    map.eval_min_distances_multi(sizes_empty_galaxies)
+        .into_iter()
+        .zip(sizes_empty_galaxies)
+        .for_each(|(res, size)| println!("expansion {size} : {res}"));
+    if let Some((c1, c2, dst)) = sizes_empty_galaxies
+        .last()
+        .and_then(|size| map.get_farthest_pair(*size))
+    {
+        print!("farthest pair : {c1:?} <-> {c2:?} : {dst}");
+    }
+}
+pub(crate) fn print_pairs(file: &str, sizes_empty_galaxies: &[usize]) {
+    let map: Map = file.into();
+    sizes_empty_galaxies.iter().for_each(|size| {
+        println!("expansion {size}");
+        map.get_pair_distances(*size)
+            .into_iter()
+            .for_each(|(c1, c2, dst)| println!("{c1:?} <-> {c2:?} : {dst}"));
+    });
+}
 
 #[cfg(test)]
 mod tests {
-    use super::{eval_file, Map};
+    use super::{eval_file, Coord, Map};
 
     fn data() -> &'static str {
         r#"
@@ -146,5 +199,28 @@ mod tests {
 
         assert_eq!(1030, map.eval_min_distances(10));
         assert_eq!(8410, map.eval_min_distances(100));
+        assert_eq!(
+            vec![374, 1030, 8410],
+            map.eval_min_distances_multi(&[2, 10, 100])
+        );
+    }
+    #[test]
+    fn test_2() {
+        let map: Map = data().into();
+        let pairs = map.get_pair_distances(2);
+
+        assert_eq!(36, pairs.len());
+        assert_eq!(374, pairs.iter().map(|(_, _, dst)| dst).sum::<usize>());
+        assert!(pairs.contains(&(Coord { x: 6, y: 1 }, Coord { x: 11, y: 5 }, 9)));
+        let farthest = pairs.iter().map(|(_, _, dst)| *dst).max();
+        assert_eq!(farthest, map.get_farthest_pair(2).map(|(_, _, dst)| dst));
+    }
+    #[test]
+    fn test_3() {
+        // Empty space keeping its size, the galaxies stay where they are.
+        let map: Map = data().into();
+        assert_eq!(292, map.eval_min_distances(1));
+        let pairs = map.get_pair_distances(1);
+        assert!(pairs.contains(&(Coord { x: 0, y: 3 }, Coord { x: 1, y: 7 }, 5)));
     }
 }
@@ -1,119 +1,256 @@
-use std::collections::HashMap;
-
-fn solve(
-    springs: Vec<char>,
-    matching_springs: Vec<usize>,
-    memo: &mut HashMap<(Vec<char>, Vec<usize>), usize>,
-) -> usize {
-    if (springs.is_empty() || springs.iter().all(|c| c == &'.' || c == &'?'))
-        && matching_springs.is_empty()
+use std::{ops::Add, thread};
+
+use num::{BigUint, One, Zero};
+
+struct Row {
+    pub(crate) springs: Vec<char>,
+    pub(crate) groups: Vec<usize>,
+}
+
+impl From<&str> for Row {
+    fn from(value: &str) -> Self {
+        let mut line = value.split(' ');
+        let springs = line
+            .next()
+            .expect("Should have springs")
+            .chars()
+            .collect::<Vec<_>>();
+        let groups = line
+            .next()
+            .expect("Should have count")
+            .split(',')
+            .map(|c| c.parse::<usize>().expect("Should be nbr"))
+            .collect::<Vec<_>>();
+        Self { springs, groups }
+    }
+}
+
+impl Row {
+    fn unfold(&self, factor: usize) -> Self {
+        let mut springs = vec![];
+        (0..factor).for_each(|i| {
+            if i != 0 {
+                springs.push('?');
+            }
+            springs.extend_from_slice(&self.springs);
+        });
+        Self {
+            springs,
+            groups: self.groups.repeat(factor),
+        }
+    }
+
+    /// `table[pos * (nbr_groups + 1) + group]` holds the number of ways to place
+    /// `groups[group..]` in `springs[pos..]`. Row `springs.len() + 1` stands for
+    /// "past the end" so that a group ending on the last spring needs no special case.
+    fn count_table<T>(&self) -> Vec<T>
+    where
+        T: Clone + Zero + One + Add<Output = T>,
     {
-        return 1;
-    } else if springs.is_empty() && !matching_springs.is_empty() {
-        return 0;
-    } else if !springs.is_empty() && matching_springs.is_empty() {
-        return 0;
+        let len = self.springs.len();
+        let nbr_groups = self.groups.len();
+        let width = nbr_groups + 1;
+
+        // Number of consecutive springs that may be damaged starting at each position.
+        let mut damageable = vec![0; len + 1];
+        (0..len).rev().for_each(|pos| {
+            if self.springs[pos] != '.' {
+                damageable[pos] = damageable[pos + 1] + 1;
+            }
+        });
+
+        let mut table = vec![T::zero(); (len + 2) * width];
+        table[len * width + nbr_groups] = T::one();
+        table[(len + 1) * width + nbr_groups] = T::one();
+        for pos in (0..len).rev() {
+            for group in 0..=nbr_groups {
+                let mut ways = T::zero();
+                if self.springs[pos] != '#' {
+                    ways = ways + table[(pos + 1) * width + group].clone();
+                }
+                if self.springs[pos] != '.' && group < nbr_groups {
+                    let size = self.groups[group];
+                    if damageable[pos] >= size
+                        && (pos + size == len || self.springs[pos + size] != '#')
+                    {
+                        ways = ways + table[(pos + size + 1) * width + group + 1].clone();
+                    }
+                }
+                table[pos * width + group] = ways;
+            }
+        }
+        table
     }
 
-    if let Some(v) = memo.get(&(springs.clone(), matching_springs.clone())) {
-        return *v;
+    fn count_arrangements<T>(&self) -> T
+    where
+        T: Clone + Zero + One + Add<Output = T>,
+    {
+        self.count_table::<T>().swap_remove(0)
     }
 
-    let mut tot = 0;
+    /// Lazily yields every arrangement in lexicographic order (`#` before `.`).
+    fn arrangements(&self) -> impl Iterator<Item = String> + '_ {
+        let table = self.count_table::<BigUint>();
+        num::range(BigUint::zero(), table[0].clone())
+            .map(move |k| self.kth_arrangement_in(&table, k))
+    }
 
-    if ['#', '?'].contains(&springs[0]) {
-        let to_check = matching_springs[0];
-        if springs.len() >= to_check
-            && (1..to_check).all(|idx| springs[idx] == '#' || springs[idx] == '?')
-            && (springs.len() == to_check || springs[to_check] != '#')
-        {
-            let cpy = if springs.len() <= to_check + 1 {
-                vec![]
+    fn kth_arrangement(&self, k: BigUint) -> Option<String> {
+        let table = self.count_table::<BigUint>();
+        (k < table[0]).then(|| self.kth_arrangement_in(&table, k))
+    }
+
+    /// Uniformly picks one of the arrangements, `None` if there is no valid one.
+    fn sample_arrangement(&self, rng: &mut XorShift) -> Option<String> {
+        let table = self.count_table::<BigUint>();
+        (!table[0].is_zero()).then(|| self.kth_arrangement_in(&table, rng.below(&table[0])))
+    }
+
+    /// Walks the counting table from the start : at each spring, the `k` first
+    /// arrangements begin with a damaged group if there are enough of them.
+    fn kth_arrangement_in(&self, table: &[BigUint], mut k: BigUint) -> String {
+        let len = self.springs.len();
+        let width = self.groups.len() + 1;
+        let mut ret = String::with_capacity(len);
+        let mut pos = 0;
+        let mut group = 0;
+        while pos < len {
+            let ways_operational = if self.springs[pos] != '#' {
+                table[(pos + 1) * width + group].clone()
             } else {
-                springs[to_check + 1..].to_vec()
+                BigUint::zero()
             };
-            let nbrs = if matching_springs.len() == 1 {
-                vec![]
+            let ways_damaged = &table[pos * width + group] - ways_operational;
+            if k < ways_damaged {
+                let size = self.groups[group];
+                (0..size).for_each(|_| ret.push('#'));
+                if pos + size < len {
+                    ret.push('.');
+                }
+                pos += size + 1;
+                group += 1;
             } else {
-                matching_springs[1..].to_vec()
-            };
-
-            tot += solve(cpy, nbrs, memo);
+                k -= ways_damaged;
+                ret.push('.');
+                pos += 1;
+            }
         }
-        if true {}
+        ret
     }
+}
+
+/// Small xorshift generator, good enough to sample arrangements while debugging.
+struct XorShift(u64);
 
-    if ['.', '?'].contains(&springs[0]) {
-        let cpy = springs[1..].to_vec();
-        tot += solve(cpy, matching_springs.clone(), memo);
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        Self(seed.max(1))
     }
-    memo.insert((springs, matching_springs), tot);
 
-    tot
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Uniform value in `0..bound`, drawing as many bits as `bound` has until
+    /// the value falls below it.
+    fn below(&mut self, bound: &BigUint) -> BigUint {
+        let bits = bound.bits();
+        let nbr_words = bits.div_ceil(64);
+        loop {
+            let bytes = (0..nbr_words)
+                .flat_map(|_| self.next_u64().to_le_bytes())
+                .collect::<Vec<_>>();
+            let v = BigUint::from_bytes_le(&bytes) >> (nbr_words * 64 - bits);
+            if &v < bound {
+                return v;
+            }
+        }
+    }
 }
 
-pub(crate) fn eval_file(file: &str) -> usize {
-    let mut memo = HashMap::new();
-    file.lines().filter(|l| !l.is_empty()).fold(0, |acc, l| {
-        let mut line = l.split(' ');
-        let springs = line
-            .next()
-            .expect("Should have springs")
-            .chars()
-            .collect::<Vec<_>>();
-        let nbrs = line
-            .next()
-            .expect("Should have count")
-            .split(',')
-            .map(|c| c.parse::<usize>().expect("Should be nbr"))
-            .collect::<Vec<_>>();
-        let ret = solve(springs, nbrs, &mut memo);
-        acc + ret
+/// Splits the lines between threads, the sum being independent of the scheduling.
+fn count_lines<T>(file: &str, factor: usize) -> T
+where
+    T: Clone + Zero + One + Add<Output = T> + Send,
+{
+    let rows = file
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(Row::from)
+        .collect::<Vec<_>>();
+    let nbr_threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = rows.len().div_ceil(nbr_threads).max(1);
+    thread::scope(|scope| {
+        rows.chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk.iter().fold(T::zero(), |acc, row| {
+                        acc + row.unfold(factor).count_arrangements::<T>()
+                    })
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .fold(T::zero(), |acc, handle| {
+                acc + handle.join().expect("Thread should not panic")
+            })
     })
 }
 
-pub(crate) fn eval_file_2(file: &str) -> usize {
-    let mut memo = HashMap::new();
-    file.lines().filter(|l| !l.is_empty()).fold(0, |acc, l| {
-        let mut line = l.split(' ');
-        let springs = line
-            .next()
-            .expect("Should have springs")
-            .chars()
-            .collect::<Vec<_>>();
-        let nbrs = line
-            .next()
-            .expect("Should have count")
-            .split(',')
-            .map(|c| c.parse::<usize>().expect("Should be nbr"))
-            .collect::<Vec<_>>();
+pub(crate) fn eval_file(file: &str) -> usize {
+    count_lines(file, 1)
+}
 
-        let mut tot_springs = vec![];
-        let mut tot_nbrs = vec![];
+pub(crate) fn eval_file_2(file: &str) -> u128 {
+    count_lines(file, 5)
+}
 
-        (0..5).for_each(|i| {
-            springs.iter().for_each(|v| tot_springs.push(*v));
-            if i != 4 {
-                tot_springs.push('?')
-            };
-            nbrs.iter().for_each(|v| tot_nbrs.push(*v));
-        });
-        // eprintln!("springs : {springs:?} // nbrs : {nbrs:?}");
-        let ret = solve(tot_springs, tot_nbrs, &mut memo);
-        // eprintln!("ret : {ret}");
-        acc + ret
-    })
+pub(crate) fn eval_file_unfolded(file: &str, factor: usize) -> BigUint {
+    count_lines(file, factor)
 }
+
 pub(crate) fn print_sol_1(file: &str) {
     print!("res : {}", eval_file(file));
 }
 pub(crate) fn print_sol_2(file: &str) {
     print!("res : {}", eval_file_2(file));
 }
+pub(crate) fn print_unfolded(file: &str, factor: usize) {
+    print!("res : {}", eval_file_unfolded(file, factor));
+}
+pub(crate) fn print_arrangements(file: &str, kth: Option<BigUint>) {
+    file.lines().filter(|l| !l.is_empty()).for_each(|l| {
+        println!("{l}");
+        let row = Row::from(l);
+        match &kth {
+            Some(k) => println!(
+                "  {}",
+                row.kth_arrangement(k.clone())
+                    .unwrap_or_else(|| "-".to_string())
+            ),
+            None => row
+                .arrangements()
+                .for_each(|arrangement| println!("  {arrangement}")),
+        }
+    });
+}
+pub(crate) fn print_samples(file: &str, factor: usize, seed: u64) {
+    let mut rng = XorShift::new(seed);
+    file.lines().filter(|l| !l.is_empty()).for_each(|l| {
+        let sample = Row::from(l).unfold(factor).sample_arrangement(&mut rng);
+        println!("{l} : {}", sample.unwrap_or_else(|| "-".to_string()));
+    });
+}
 
 #[cfg(test)]
 mod tests {
-    use super::{eval_file, eval_file_2};
+    use num::BigUint;
+
+    use super::{eval_file, eval_file_2, eval_file_unfolded, Row, XorShift};
 
     fn data() -> &'static str {
         r#"
@@ -132,4 +269,72 @@ mod tests {
     fn test_1() {
         assert_eq!(525152, eval_file_2(data()));
     }
+    #[test]
+    fn test_2() {
+        let counts = data()
+            .lines()
+            .filter(|l| !l.is_empty())
+            .map(|l| Row::from(l).unfold(5).count_arrangements::<u64>())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![1, 16384, 1, 16, 2500, 506250], counts);
+    }
+    #[test]
+    fn test_3() {
+        assert_eq!(BigUint::from(21u32), eval_file_unfolded(data(), 1));
+        assert_eq!(BigUint::from(525152u32), eval_file_unfolded(data(), 5));
+        // The last line grows as 10 * 15^(n - 1), well past a u128 at 40 copies.
+        assert!(eval_file_unfolded(data(), 40) > BigUint::from(u128::MAX));
+    }
+    #[test]
+    fn test_4() {
+        let row = Row::from("?###???????? 3,2,1");
+        let arrangements = row.arrangements().collect::<Vec<_>>();
+        assert_eq!(10, arrangements.len());
+        assert_eq!(".###.##.#...", arrangements[0]);
+        assert_eq!(".###....##.#", arrangements[9]);
+        assert!(arrangements.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(
+            Some(arrangements[3].clone()),
+            row.kth_arrangement(BigUint::from(3u32))
+        );
+        assert_eq!(None, row.kth_arrangement(BigUint::from(10u32)));
+    }
+    #[test]
+    fn test_5() {
+        let row = Row::from("???.### 1,1,3");
+        assert_eq!(vec!["#.#.###"], row.arrangements().collect::<Vec<_>>());
+
+        let row = Row::from(".??..??...?##. 1,1,3");
+        let arrangements = row.arrangements().collect::<Vec<_>>();
+        let mut rng = XorShift::new(42);
+        let mut seen = vec![0; arrangements.len()];
+        (0..400).for_each(|_| {
+            let sample = row.sample_arrangement(&mut rng).expect("Has arrangements");
+            let idx = arrangements.iter().position(|a| a == &sample);
+            seen[idx.expect("Sample should be a valid arrangement")] += 1;
+        });
+        assert!(seen.iter().all(|nbr| *nbr > 50));
+    }
+    #[test]
+    fn test_6() {
+        // 10 * 15^39 arrangements, far more than a u128 can hold.
+        let row = Row::from("?###???????? 3,2,1").unfold(40);
+        let total = row.count_arrangements::<BigUint>();
+        assert!(total > BigUint::from(u128::MAX));
+        let last = row
+            .kth_arrangement(total.clone() - 1u32)
+            .expect("Should have a last arrangement");
+        assert_eq!(row.springs.len(), last.len());
+        assert_eq!(None, row.kth_arrangement(total));
+        let mut rng = XorShift::new(7);
+        let sample = row.sample_arrangement(&mut rng).expect("Has arrangements");
+        assert_eq!(row.springs.len(), sample.len());
+        let groups = sample
+            .split('.')
+            .filter(|g| !g.is_empty())
+            .map(|g| g.len())
+            .collect::<Vec<_>>();
+        assert_eq!(row.groups, groups);
+        assert!(row.arrangements().next().is_some());
+    }
 }
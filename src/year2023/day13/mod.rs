@@ -24,68 +24,103 @@ impl Deref for Map {
     }
 }
 
-impl Map {
-    fn find_mirrored_col(&self) -> Option<usize> {
-        let col_nbr = self[0].len();
-        let v = (0..(col_nbr - 1)).find(|idx| {
-            let diff = col_nbr - idx;
-            let max_dst = (idx + 1).min(diff - 1);
-            (0..self.len()).all(|x| (0..max_dst).all(|y| self[x][idx - y] == self[x][idx + y + 1]))
-        });
-        v.map(|v| v + 1)
-    }
-    fn find_mirrored_line(&self) -> Option<usize> {
-        let line_nbr = self.len();
-        let v = (0..(line_nbr - 1)).find(|idx| {
-            let diff = line_nbr - idx;
-            let max_dst = (idx + 1).min(diff - 1);
-            (0..self[0].len())
-                .all(|y| (0..max_dst).all(|x| self[idx - x][y] == self[idx + x + 1][y]))
-        });
-        v.map(|v| (v + 1) * 100)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Axis {
+    /// Mirror between two columns
+    Vertical,
+    /// Mirror between two rows
+    Horizontal,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Reflection {
+    pub(crate) axis: Axis,
+    /// Number of columns left of the mirror, or rows above it.
+    pub(crate) idx: usize,
+    /// `(row, col)` of the cells that had to be flipped, taken on the left/top side.
+    pub(crate) smudges: Vec<(usize, usize)>,
+}
+
+impl Reflection {
+    fn summary(&self) -> usize {
+        match self.axis {
+            Axis::Vertical => self.idx,
+            Axis::Horizontal => self.idx * 100,
+        }
     }
+}
 
-    fn find_col_with_one_error(&self) -> Option<usize> {
-        let col_nbr = self[0].len();
-        let v = (0..(col_nbr - 1)).find(|idx| {
-            let diff = col_nbr - idx;
-            let max_dst = (idx + 1).min(diff - 1);
-            let mut to_keep = false;
-            for x in 0..self.len() {
-                for y in 0..max_dst {
-                    if self[x][idx - y] == self[x][idx + y + 1] {
-                        continue;
-                    } else if !to_keep {
-                        to_keep = true
-                    } else {
-                        return false;
+/// Finds every mirror between two consecutive lines for which the mirrored
+/// lines differ on exactly `nbr_smudges` cells, with the position of those cells.
+fn find_mirrors(lines: &[Vec<u64>], nbr_smudges: usize) -> Vec<(usize, Vec<(usize, usize)>)> {
+    (1..lines.len())
+        .filter_map(|idx| {
+            let mut smudges = vec![];
+            for (before, after) in (0..idx).rev().zip(idx..lines.len()) {
+                for (w, (a, b)) in lines[before].iter().zip(lines[after].iter()).enumerate() {
+                    let mut diff = a ^ b;
+                    while diff != 0 {
+                        if smudges.len() == nbr_smudges {
+                            return None;
+                        }
+                        smudges.push((before, w * 64 + diff.trailing_zeros() as usize));
+                        diff &= diff - 1;
                     }
                 }
             }
-            to_keep
+            (smudges.len() == nbr_smudges).then_some((idx, smudges))
+        })
+        .collect()
+}
+
+/// Bitmask of the `#` among `cells`, as many 64 bits words as needed.
+fn mask(cells: impl ExactSizeIterator<Item = char>) -> Vec<u64> {
+    let mut ret = vec![0; cells.len().div_ceil(64)];
+    cells
+        .enumerate()
+        .filter(|(_, c)| *c == '#')
+        .for_each(|(i, _)| {
+            ret[i / 64] |= 1 << (i % 64);
         });
-        v.map(|v| v + 1)
+    ret
+}
+
+impl Map {
+    /// Each row as a bitmask, bit `y` being set for a `#` in column `y`.
+    fn rows_mask(&self) -> Vec<Vec<u64>> {
+        self.iter().map(|line| mask(line.iter().copied())).collect()
     }
-    fn find_row_with_one_error(&self) -> Option<usize> {
-        let row_nbr = self.len();
-        let v = (0..(row_nbr - 1)).find(|idx| {
-            let diff = row_nbr - idx;
-            let max_dst = (idx + 1).min(diff - 1);
-            let mut to_keep = false;
-            for y in 0..self[0].len() {
-                for x in 0..max_dst {
-                    if self[idx - x][y] == self[idx + x + 1][y] {
-                        continue;
-                    } else if !to_keep {
-                        to_keep = true
-                    } else {
-                        return false;
-                    }
-                }
-            }
-            to_keep
-        });
-        v.map(|v| (v + 1) * 100)
+
+    /// Each column as a bitmask, bit `x` being set for a `#` in row `x`.
+    fn cols_mask(&self) -> Vec<Vec<u64>> {
+        (0..self[0].len())
+            .map(|y| mask(self.iter().map(|line| line[y])))
+            .collect()
+    }
+
+    fn find_reflections(&self, nbr_smudges: usize) -> Vec<Reflection> {
+        let vertical = find_mirrors(&self.cols_mask(), nbr_smudges)
+            .into_iter()
+            .map(|(idx, smudges)| Reflection {
+                axis: Axis::Vertical,
+                idx,
+                smudges: smudges.into_iter().map(|(y, x)| (x, y)).collect(),
+            });
+        let horizontal = find_mirrors(&self.rows_mask(), nbr_smudges)
+            .into_iter()
+            .map(|(idx, smudges)| Reflection {
+                axis: Axis::Horizontal,
+                idx,
+                smudges,
+            });
+        vertical.chain(horizontal).collect()
+    }
+
+    fn summarize(&self, nbr_smudges: usize) -> usize {
+        self.find_reflections(nbr_smudges)
+            .first()
+            .expect("Should have a mirror")
+            .summary()
     }
 }
 
@@ -113,28 +148,12 @@ impl From<&str> for Maps {
 
 pub(crate) fn eval_file(file: &str) -> usize {
     let maps: Maps = file.into();
-    maps.iter().fold(0, |acc, map| {
-        if let Some(v) = map.find_mirrored_col() {
-            acc + v
-        } else if let Some(v) = map.find_mirrored_line() {
-            acc + v
-        } else {
-            unreachable!()
-        }
-    })
+    maps.iter().map(|map| map.summarize(0)).sum()
 }
 
 pub(crate) fn eval_file_2(file: &str) -> usize {
     let maps: Maps = file.into();
-    maps.iter().fold(0, |acc, map| {
-        if let Some(v) = map.find_col_with_one_error() {
-            acc + v
-        } else if let Some(v) = map.find_row_with_one_error() {
-            acc + v
-        } else {
-            unreachable!()
-        }
-    })
+    maps.iter().map(|map| map.summarize(1)).sum()
 }
 pub(crate) fn print_sol_1(file: &str) {
     print!("res : {}", eval_file(file));
@@ -145,7 +164,7 @@ pub(crate) fn print_sol_2(file: &str) {
 
 #[cfg(test)]
 mod tests {
-    use super::{eval_file, eval_file_2};
+    use super::{eval_file, eval_file_2, Axis, Maps, Reflection};
 
     fn data() -> &'static str {
         r#"#.##..##.
@@ -173,4 +192,48 @@ mod tests {
     fn test_1() {
         assert_eq!(400, eval_file_2(data()));
     }
+    #[test]
+    fn test_2() {
+        let maps: Maps = data().into();
+        assert_eq!(
+            vec![Reflection {
+                axis: Axis::Horizontal,
+                idx: 3,
+                smudges: vec![(0, 0)],
+            }],
+            maps[0].find_reflections(1)
+        );
+        assert_eq!(
+            vec![Reflection {
+                axis: Axis::Horizontal,
+                idx: 1,
+                smudges: vec![(0, 4)],
+            }],
+            maps[1].find_reflections(1)
+        );
+        assert!(maps[0]
+            .find_reflections(2)
+            .iter()
+            .all(|r| r.smudges.len() == 2));
+    }
+    /// 70 columns, the mirror sitting between the 67th and the 68th one.
+    fn data_2() -> &'static str {
+        r#"#.##.##.##.##.#..#.######.##..#.#..#..#######.##..#.#......#..###.##.#
+#.###...#.#......###.#.#.##...#.#.#.##.##.###.##.###...######.###.##.#
+###.......#.#.#..#.###...##.##.#...#.#...##.#..#..#.#.#.#.####.##.##.#"#
+    }
+    #[test]
+    fn test_3() {
+        assert_eq!(67, eval_file(data_2()));
+        let smudged = data_2().replacen(".##.#\n###", ".##..\n###", 1);
+        let maps: Maps = smudged.as_str().into();
+        assert_eq!(
+            vec![Reflection {
+                axis: Axis::Vertical,
+                idx: 67,
+                smudges: vec![(1, 64)],
+            }],
+            maps[0].find_reflections(1)
+        );
+    }
 }
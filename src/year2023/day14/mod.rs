@@ -1,205 +1,367 @@
-use std::{
-    collections::HashMap,
-    fmt::Debug,
-    ops::{Deref, DerefMut},
-};
+use std::fmt::Debug;
 
-#[derive(Eq, Hash, PartialEq, Clone)]
-struct Map(Vec<Vec<char>>);
+use crate::cycle::{self, Strategy};
 
 const NBR_CYCLES: usize = 1_000_000_000;
+const SPIN_CYCLE: [Tilt; 4] = [Tilt::North, Tilt::West, Tilt::South, Tilt::East];
+/// Blocks repeated at most this many times are run without looking for a cycle.
+const MAX_REPEAT_WITHOUT_CYCLE: usize = 16;
 
-impl Debug for Map {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.iter().for_each(|line| {
-            line.iter().for_each(|c| {
-                let _ = write!(f, "{}", c);
-            });
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct BitSet(Vec<u64>);
+
+impl BitSet {
+    fn new(len: usize) -> Self {
+        Self(vec![0; len.div_ceil(64)])
+    }
+
+    fn get(&self, idx: usize) -> bool {
+        self.0[idx / 64] >> (idx % 64) & 1 == 1
+    }
 
-            let _ = write!(f, "\n");
+    fn set(&mut self, idx: usize) {
+        self.0[idx / 64] |= 1 << (idx % 64);
+    }
+
+    fn clear(&mut self) {
+        self.0.iter_mut().for_each(|w| *w = 0);
+    }
+
+    fn count(&self) -> usize {
+        self.0.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Calls `f` on every word touched by `start..end`, with the mask of the bits in range.
+    fn for_each_word(&mut self, start: usize, end: usize, mut f: impl FnMut(&mut u64, u64)) {
+        if start >= end {
+            return;
+        }
+        let (first, last) = (start / 64, (end - 1) / 64);
+        (first..=last).for_each(|w| {
+            let low = if w == first { start % 64 } else { 0 };
+            let high = if w == last { (end - 1) % 64 + 1 } else { 64 };
+            let mask = (u64::MAX >> (64 - (high - low))) << low;
+            f(&mut self.0[w], mask);
         });
-        Ok(())
     }
-}
 
-impl From<&str> for Map {
-    fn from(value: &str) -> Self {
-        Map(value
-            .lines()
-            .filter(|l| !l.is_empty())
-            .map(|l| l.chars().collect())
-            .collect())
+    fn count_range(&mut self, start: usize, end: usize) -> usize {
+        let mut tot = 0;
+        self.for_each_word(start, end, |w, mask| {
+            tot += (*w & mask).count_ones() as usize
+        });
+        tot
+    }
+
+    fn set_range(&mut self, start: usize, end: usize) {
+        self.for_each_word(start, end, |w, mask| *w |= mask);
     }
-}
 
-impl Deref for Map {
-    type Target = Vec<Vec<char>>;
-    fn deref(&self) -> &Self::Target {
-        &self.0
+    fn iter_ones(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().enumerate().flat_map(|(i, w)| {
+            let mut w = *w;
+            std::iter::from_fn(move || {
+                (w != 0).then(|| {
+                    let bit = w.trailing_zeros() as usize;
+                    w &= w - 1;
+                    i * 64 + bit
+                })
+            })
+        })
     }
 }
-impl DerefMut for Map {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Tilt {
+    North,
+    West,
+    South,
+    East,
+}
+
+impl From<char> for Tilt {
+    fn from(value: char) -> Self {
+        match value.to_ascii_uppercase() {
+            'N' => Self::North,
+            'W' => Self::West,
+            'S' => Self::South,
+            'E' => Self::East,
+            c => panic!("Unknown tilt {c}, expected one of N, W, S or E"),
+        }
     }
 }
 
-impl Map {
-    fn push_north(&mut self) {
-        let h = self.len();
-        let w = self[0].len();
-        for y in 0..w {
-            let mut last_block = 0;
-            for x in 0..h {
-                let v = self[x][y];
-                if v == 'O' {
-                    if last_block != x {
-                        self[last_block][y] = 'O';
-                        self[x][y] = '.';
-                    }
-                    last_block += 1;
-                } else if v == '#' {
-                    last_block = x + 1;
+/// Blocks of tilts, each one repeated a number of times : `SW*1000` tilts south
+/// then west a thousand times, `NNE` is a single block run once.
+#[derive(Debug, PartialEq)]
+struct TiltProgram(Vec<(Vec<Tilt>, usize)>);
+
+impl From<&str> for TiltProgram {
+    fn from(value: &str) -> Self {
+        let mut blocks = vec![];
+        let mut chars = value.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c.is_whitespace() || c == ',' {
+                continue;
+            }
+            let mut tilts = vec![c.into()];
+            while let Some(c) = chars.next_if(|c| c.is_ascii_alphabetic()) {
+                tilts.push(c.into());
+            }
+            let mut repeat = 1;
+            if chars.next_if_eq(&'*').is_some() {
+                let mut nbr = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || c == &'_') {
+                    nbr.push(c);
                 }
+                repeat = nbr
+                    .replace('_', "")
+                    .parse()
+                    .expect("Should be a repeat count");
             }
+            blocks.push((tilts, repeat));
         }
+        Self(blocks)
     }
+}
 
-    fn push_west(&mut self) {
-        let h = self.len();
-        let w = self[0].len();
-        for x in 0..h {
-            let mut last_block = 0;
-            for y in 0..w {
-                let v = self[x][y];
-                if v == 'O' {
-                    if last_block != y {
-                        self[x][last_block] = 'O';
-                        self[x][y] = '.';
-                    }
-                    last_block += 1;
-                } else if v == '#' {
-                    last_block = y + 1;
-                }
+/// Size of the platform and its cube rocks, only kept through the free segments
+/// they delimit. None of it moves when tilting.
+struct Frame {
+    pub(crate) height: usize,
+    pub(crate) width: usize,
+    pub(crate) cube_rows: Vec<BitSet>,
+    /// Free `start..end` ranges of each row, between cube rocks.
+    pub(crate) row_segments: Vec<Vec<(usize, usize)>>,
+    /// Free `start..end` ranges of each column, between cube rocks.
+    pub(crate) col_segments: Vec<Vec<(usize, usize)>>,
+}
+
+/// Platform stored as bitsets : round rocks per row and per column, kept in
+/// sync after each tilt, on top of the fixed frame.
+struct Platform {
+    pub(crate) frame: Frame,
+    pub(crate) round_rows: Vec<BitSet>,
+    pub(crate) round_cols: Vec<BitSet>,
+}
+
+fn free_segments(len: usize, is_cube: impl Fn(usize) -> bool) -> Vec<(usize, usize)> {
+    let mut ret = vec![];
+    let mut start = 0;
+    (0..=len).for_each(|idx| {
+        if idx == len || is_cube(idx) {
+            if start < idx {
+                ret.push((start, idx));
             }
+            start = idx + 1;
         }
+    });
+    ret
+}
+
+impl Debug for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        (0..self.frame.height).try_for_each(|x| {
+            (0..self.frame.width).try_for_each(|y| {
+                let c = if self.round_rows[x].get(y) {
+                    'O'
+                } else if self.frame.cube_rows[x].get(y) {
+                    '#'
+                } else {
+                    '.'
+                };
+                write!(f, "{}", c)
+            })?;
+            writeln!(f)
+        })
     }
+}
 
-    fn push_south(&mut self) {
-        let h = self.len();
-        let w = self[0].len();
-        for y in 0..w {
-            let mut last_block = h - 1;
-            for x in (0..h).rev() {
-                let v = self[x][y];
-                if v == 'O' {
-                    if last_block != x {
-                        self[last_block][y] = 'O';
-                        self[x][y] = '.';
-                    }
-                    if last_block != 0 {
-                        last_block -= 1;
-                    }
-                } else if v == '#' {
-                    if x != 0 {
-                        last_block = x - 1;
-                    }
+impl From<&str> for Platform {
+    fn from(value: &str) -> Self {
+        let lines = value
+            .lines()
+            .filter(|l| !l.is_empty())
+            .map(|l| l.chars().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let height = lines.len();
+        let width = lines[0].len();
+        let mut round_rows = vec![BitSet::new(width); height];
+        let mut round_cols = vec![BitSet::new(height); width];
+        let mut cube_rows = vec![BitSet::new(width); height];
+        lines.iter().enumerate().for_each(|(x, line)| {
+            line.iter().enumerate().for_each(|(y, c)| match c {
+                'O' => {
+                    round_rows[x].set(y);
+                    round_cols[y].set(x);
                 }
-            }
+                '#' => cube_rows[x].set(y),
+                '.' => (),
+                _ => unreachable!(),
+            })
+        });
+        let row_segments = (0..height)
+            .map(|x| free_segments(width, |y| cube_rows[x].get(y)))
+            .collect();
+        let col_segments = (0..width)
+            .map(|y| free_segments(height, |x| cube_rows[x].get(y)))
+            .collect();
+        Self {
+            frame: Frame {
+                height,
+                width,
+                cube_rows,
+                row_segments,
+                col_segments,
+            },
+            round_rows,
+            round_cols,
         }
     }
+}
 
-    fn push_east(&mut self) {
-        let h = self.len();
-        let w = self[0].len();
-        for x in 0..h {
-            let mut last_block = w - 1;
-            for y in (0..w).rev() {
-                let v = self[x][y];
-                if v == 'O' {
-                    if last_block != y {
-                        self[x][last_block] = 'O';
-                        self[x][y] = '.';
-                    }
-                    if last_block != 0 {
-                        last_block -= 1;
-                    }
-                } else if v == '#' {
-                    if y != 0 {
-                        last_block = y - 1;
-                    }
-                }
+/// Packs the rocks of every segment of `lines` at its start, or at its end if `to_end`.
+fn slide(lines: &mut [BitSet], segments: &[Vec<(usize, usize)>], to_end: bool) {
+    lines.iter_mut().zip(segments).for_each(|(line, segments)| {
+        let counts = segments
+            .iter()
+            .map(|(start, end)| line.count_range(*start, *end))
+            .collect::<Vec<_>>();
+        line.clear();
+        segments.iter().zip(counts).for_each(|((start, end), nbr)| {
+            if to_end {
+                line.set_range(end - nbr, *end)
+            } else {
+                line.set_range(*start, start + nbr)
+            }
+        });
+    });
+}
+
+/// Rebuilds the transposed bitsets from `lines`, in time linear in the number of rocks.
+fn transpose(lines: &[BitSet], transposed: &mut [BitSet]) {
+    transposed.iter_mut().for_each(|t| t.clear());
+    lines.iter().enumerate().for_each(|(i, line)| {
+        line.iter_ones().for_each(|j| transposed[j].set(i));
+    });
+}
+
+impl Frame {
+    fn tilt(&self, rows: &mut [BitSet], cols: &mut [BitSet], tilt: Tilt) {
+        match tilt {
+            Tilt::North | Tilt::South => {
+                slide(cols, &self.col_segments, matches!(tilt, Tilt::South));
+                transpose(cols, rows);
+            }
+            Tilt::West | Tilt::East => {
+                slide(rows, &self.row_segments, matches!(tilt, Tilt::East));
+                transpose(rows, cols);
             }
         }
     }
 
-    fn get_weight(&self) -> usize {
-        let h = self.len();
-        let w = self[0].len();
-        (0..w).fold(0, |acc, y| {
-            let mut tot = 0;
-            let mut last_block = 0;
-            (0..h).for_each(|x| {
-                match self[x][y] {
-                    'O' => {
-                        tot += h - last_block;
-                        last_block += 1
-                    }
-                    '#' => last_block = x + 1,
-                    '.' => (),
-                    _ => unreachable!(),
-                };
-            });
-            acc + tot
-        })
+    /// Round rocks of each row once `tilts` ran from `rows`, the only part of
+    /// the platform that changes, so that cycles are looked for on it alone.
+    fn spin(&self, rows: &[BitSet], tilts: &[Tilt]) -> Vec<BitSet> {
+        let mut rows = rows.to_vec();
+        let mut cols = vec![BitSet::new(self.height); self.width];
+        transpose(&rows, &mut cols);
+        tilts
+            .iter()
+            .for_each(|tilt| self.tilt(&mut rows, &mut cols, *tilt));
+        rows
     }
-    fn get_weight_no_move(&self) -> usize {
-        let h = self.len();
-        self.iter().enumerate().fold(0, |acc, (x, line)| {
-            acc + line
-                .iter()
-                .fold(0, |tot, c| tot + if c == &'O' { h - x } else { 0 })
-        })
+
+    /// Load on the `edge` support beams : each round rock weighs its distance to the opposite edge.
+    fn get_load(&self, rows: &[BitSet], edge: Tilt) -> usize {
+        rows.iter()
+            .enumerate()
+            .map(|(x, row)| match edge {
+                Tilt::North => (self.height - x) * row.count(),
+                Tilt::South => (x + 1) * row.count(),
+                Tilt::West => row.iter_ones().map(|y| self.width - y).sum(),
+                Tilt::East => row.iter_ones().map(|y| y + 1).sum(),
+            })
+            .sum()
     }
+}
 
-    fn do_cycle(&mut self) {
-        self.push_north();
-        self.push_west();
-        self.push_south();
-        self.push_east();
+impl Platform {
+    fn tilt(&mut self, tilt: Tilt) {
+        self.frame
+            .tilt(&mut self.round_rows, &mut self.round_cols, tilt);
     }
 
-    fn excecute_rotations(&mut self) -> usize {
-        let mut memo = HashMap::new();
-        let mut cycle = 0;
+    fn get_load(&self, edge: Tilt) -> usize {
+        self.frame.get_load(&self.round_rows, edge)
+    }
+
+    fn get_weight(&self) -> usize {
+        let rows = self.frame.spin(&self.round_rows, &[Tilt::North]);
+        self.frame.get_load(&rows, Tilt::North)
+    }
+
+    fn set_rows(&mut self, rows: Vec<BitSet>) {
+        self.round_rows = rows;
+        transpose(&self.round_rows, &mut self.round_cols);
+    }
 
-        while cycle < NBR_CYCLES {
-            if let Some(past_cycle) = memo.insert(self.clone(), cycle) {
-                let remaining = NBR_CYCLES - cycle - 1;
-                let to_find = remaining % (cycle - past_cycle);
-                let seen = memo
-                    .iter()
-                    .find(|(_, idx)| **idx == to_find + past_cycle + 1)
-                    .expect("Should be present")
-                    .0;
+    fn excecute_rotations(&mut self, nbr_cycles: usize, tilts: &[Tilt]) {
+        let rows = cycle::state_at(
+            Strategy::Hash,
+            &self.round_rows,
+            |rows: &Vec<BitSet>| self.frame.spin(rows, tilts),
+            nbr_cycles,
+        );
+        self.set_rows(rows);
+    }
 
-                return seen.get_weight_no_move();
+    /// Runs every block of `program`, skipping the repetitions that would only
+    /// loop over already seen states. `on_step` sees the platform after each
+    /// tilt, `on_skip` the number of skipped repetitions and the cycle length.
+    fn run_program(
+        &mut self,
+        program: &TiltProgram,
+        mut on_step: impl FnMut(Tilt, &Self),
+        mut on_skip: impl FnMut(usize, usize),
+    ) {
+        for (tilts, repeat) in program.0.iter() {
+            let mut run = |platform: &mut Self, nbr_repeat: usize| {
+                (0..nbr_repeat).for_each(|_| {
+                    tilts.iter().for_each(|tilt| {
+                        platform.tilt(*tilt);
+                        on_step(*tilt, platform);
+                    })
+                })
+            };
+            if *repeat <= MAX_REPEAT_WITHOUT_CYCLE {
+                run(self, *repeat);
+                continue;
             }
-            self.do_cycle();
-            cycle += 1;
+            // Only the length of the cycle matters here, no need to keep a
+            // fingerprint of every state.
+            let cycle = cycle::find_cycle_with(Strategy::Brent, &self.round_rows, |rows| {
+                self.frame.spin(rows, tilts)
+            });
+            let nbr_repeat = cycle.reduce(*repeat);
+            run(self, nbr_repeat.min(cycle.prefix));
+            if nbr_repeat < *repeat {
+                on_skip(*repeat - nbr_repeat, cycle.period);
+            }
+            run(self, nbr_repeat.saturating_sub(cycle.prefix));
         }
-        unreachable!()
     }
 }
 
 pub(crate) fn eval_file(file: &str) -> usize {
-    let map: Map = file.into();
-    map.get_weight()
+    let platform: Platform = file.into();
+    platform.get_weight()
 }
 
 pub(crate) fn eval_file_2(file: &str) -> usize {
-    let mut map: Map = file.into();
-    map.excecute_rotations()
+    let mut platform: Platform = file.into();
+    platform.excecute_rotations(NBR_CYCLES, &SPIN_CYCLE);
+    platform.get_load(Tilt::North)
 }
 pub(crate) fn print_sol_1(file: &str) {
     print!("res : {}", eval_file(file));
@@ -207,10 +369,28 @@ pub(crate) fn print_sol_1(file: &str) {
 pub(crate) fn print_sol_2(file: &str) {
     print!("res : {}", eval_file_2(file));
 }
+pub(crate) fn print_program(file: &str, program: &str, edge: char, trace: bool) {
+    let mut platform: Platform = file.into();
+    let program: TiltProgram = program.into();
+    platform.run_program(
+        &program,
+        |tilt, platform| {
+            if trace {
+                println!("{tilt:?}\n{platform:?}");
+            }
+        },
+        |nbr_repeat, period| {
+            if trace {
+                println!("skipping {nbr_repeat} repetitions (cycle of {period})\n");
+            }
+        },
+    );
+    print!("res : {}", platform.get_load(edge.into()));
+}
 
 #[cfg(test)]
 mod tests {
-    use super::{eval_file, eval_file_2};
+    use super::{eval_file, eval_file_2, Platform, Tilt, TiltProgram, SPIN_CYCLE};
 
     fn data() -> &'static str {
         r#"
@@ -233,4 +413,83 @@ O.#..O.#.#
     fn test_1() {
         assert_eq!(64, eval_file_2(data()));
     }
+    fn spin(platform: &mut Platform) {
+        SPIN_CYCLE.iter().for_each(|tilt| platform.tilt(*tilt));
+    }
+    #[test]
+    fn test_2() {
+        let mut platform: Platform = data().into();
+        spin(&mut platform);
+        assert_eq!(
+            r#".....#....
+....#...O#
+...OO##...
+.OO#......
+.....OOO#.
+.O#...O#.#
+....O#....
+......OOOO
+#...O###..
+#..OO#....
+"#,
+            format!("{platform:?}")
+        );
+        spin(&mut platform);
+        spin(&mut platform);
+        assert_eq!(69, platform.get_load(Tilt::North));
+    }
+    #[test]
+    fn test_3() {
+        assert_eq!(
+            TiltProgram(vec![
+                (vec![Tilt::North, Tilt::North, Tilt::East], 1),
+                (vec![Tilt::South, Tilt::West], 1000),
+            ]),
+            "NNE SW*1000".into()
+        );
+        assert_eq!(
+            TiltProgram(vec![(SPIN_CYCLE.to_vec(), 1_000_000_000)]),
+            "NWSE*1_000_000_000".into()
+        );
+    }
+    #[test]
+    fn test_4() {
+        let mut platform: Platform = data().into();
+        let program: TiltProgram = "NWSE*1000000000".into();
+        let mut nbr_steps = 0;
+        let mut skipped = vec![];
+        platform.run_program(
+            &program,
+            |_, _| nbr_steps += 1,
+            |nbr_repeat, period| skipped.push((nbr_repeat, period)),
+        );
+        assert_eq!(64, platform.get_load(Tilt::North));
+        assert!(nbr_steps < 4 * 20);
+        assert_eq!(1, skipped.len());
+        assert_eq!(7, skipped[0].1);
+        assert_eq!(1_000_000_000, skipped[0].0 + nbr_steps / 4);
+
+        let run = |program: &str| {
+            let mut platform: Platform = data().into();
+            let mut nbr_steps = 0;
+            platform.run_program(
+                &program.into(),
+                |_, _| nbr_steps += 1,
+                |_, _| panic!("Should not look for a cycle"),
+            );
+            (platform, nbr_steps)
+        };
+        let (north, nbr_steps) = run("N");
+        assert_eq!(136, north.get_load(Tilt::North));
+        assert_eq!(1, nbr_steps);
+        let (east, _) = run("E");
+        let nbr_rocks = east.round_rows.iter().map(|r| r.count()).sum::<usize>();
+        assert_eq!(
+            nbr_rocks * (east.frame.width + 1),
+            east.get_load(Tilt::East) + east.get_load(Tilt::West)
+        );
+        let (east_3, nbr_steps) = run("E*3");
+        assert_eq!(east.get_load(Tilt::East), east_3.get_load(Tilt::East));
+        assert_eq!(3, nbr_steps);
+    }
 }
@@ -1,79 +1,294 @@
-fn convert_char(v: u64, c: char) -> u64 {
-    let code: u64 = c.into();
-    ((v + code) * 17) % 256
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+};
+
+/// The HASH algorithm : starting from `seed`, every character code is added
+/// then the value is multiplied by `multiplier` and reduced modulo `modulus`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LabelHasher {
+    pub(crate) multiplier: u64,
+    pub(crate) modulus: u64,
+    pub(crate) seed: u64,
 }
 
-fn hash(part: &str) -> u64 {
-    part.chars().fold(0, |ascii, c| convert_char(ascii, c))
+impl Default for LabelHasher {
+    fn default() -> Self {
+        Self {
+            multiplier: 17,
+            modulus: 256,
+            seed: 0,
+        }
+    }
+}
+
+impl LabelHasher {
+    /// Both factors are reduced below the modulus before multiplying, so that
+    /// their product always fits in a `u128`.
+    fn convert_char(&self, v: u64, c: char) -> u64 {
+        let modulus = self.modulus as u128;
+        let sum = (v as u128 + u128::from(u32::from(c))) % modulus;
+        (sum * (self.multiplier as u128 % modulus) % modulus) as u64
+    }
+
+    fn hash(&self, part: &str) -> u64 {
+        part.chars().fold(self.seed % self.modulus, |ascii, c| {
+            self.convert_char(ascii, c)
+        })
+    }
 }
 
 pub(crate) fn eval_file(file: &str) -> u64 {
+    let hasher = LabelHasher::default();
     file.split(',')
         .filter(|s| !s.is_empty())
-        .fold(0, |acc, part| acc + hash(part))
+        .fold(0, |acc, part| acc + hasher.hash(part))
 }
 
-pub(crate) fn eval_file_2(file: &str) -> usize {
-    let mut boxes = (0..256)
-        .map(|_| Vec::<(String, u32)>::new())
-        .collect::<Vec<_>>();
-    file.split(',').filter(|s| !s.is_empty()).for_each(|v| {
-        let mut label = String::new();
-        let mut chars = v.chars();
-        let mut idx = 0;
-        while let Some(c) = chars.next() {
-            if c.is_alphabetic() {
-                label.push(c);
-                idx = convert_char(idx, c);
-            } else {
-                let b = boxes.get_mut(idx as usize).expect("Is present");
-                let idx = b
-                    .iter()
-                    .enumerate()
-                    .find_map(|(i, (v, _))| if v == &label { Some(i) } else { None });
-                match c {
-                    '-' => {
-                        if let Some(idx) = idx {
-                            b.remove(idx);
-                        }
-                        return;
-                    }
-                    '=' => {
-                        let mut nbr = 0;
-                        while let Some(v) = chars.next() {
-                            nbr = nbr * 10 + v.to_digit(10).expect("Should be nbr");
-                        }
-                        if let Some(idx) = idx {
-                            b[idx].1 = nbr;
-                        } else {
-                            b.push((label, nbr));
-                        }
-                        // match map.entry(label) {
-                        //     Entry::Occupied(mut occ) => occ.get_mut().0 = nbr,
-                        //     Entry::Vacant(v) => {
-                        //         v.insert((nbr, len));
-                        //     }
-                        // }
-                        // map.entry(label)
-                        //     .and_modify(|(l, _)| *l = nbr)
-                        //     .or_insert((nbr, len));
-                        return;
-                    }
-                    _ => unreachable!(),
-                }
+#[derive(Debug, PartialEq)]
+enum Step<'a> {
+    /// `label=N`
+    Insert { label: &'a str, focal_length: u32 },
+    /// `label-`
+    Remove { label: &'a str },
+}
+
+impl<'a> TryFrom<&'a str> for Step<'a> {
+    type Error = String;
+    fn try_from(value: &'a str) -> Result<Self, Self::Error> {
+        let value = value.trim();
+        if let Some(label) = value.strip_suffix('-') {
+            Ok(Self::Remove { label })
+        } else if let Some((label, focal_length)) = value.split_once('=') {
+            let focal_length = focal_length
+                .parse()
+                .map_err(|_| format!("Invalid focal length in step {value:?}"))?;
+            Ok(Self::Insert {
+                label,
+                focal_length,
+            })
+        } else {
+            Err(format!("Step {value:?} is neither `label=N` nor `label-`"))
+        }
+    }
+}
+
+#[derive(Default)]
+struct LensBox {
+    /// Lenses in insertion order, `None` for the removed ones until the next compaction.
+    slots: Vec<Option<(String, u32)>>,
+    len: usize,
+}
+
+/// The boxes of the HASHMAP procedure, only the ones holding lenses being
+/// allocated, with every label indexed to its `(box, slot)` so that finding a
+/// lens never scans a box.
+struct LensBoxes {
+    hasher: LabelHasher,
+    boxes: BTreeMap<u64, LensBox>,
+    index: HashMap<String, (u64, usize)>,
+}
+
+impl Default for LensBoxes {
+    fn default() -> Self {
+        Self::new(LabelHasher::default())
+    }
+}
+
+impl LensBoxes {
+    fn new(hasher: LabelHasher) -> Self {
+        Self {
+            hasher,
+            boxes: BTreeMap::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    /// Like `HashMap::insert`, returns the focal length of the replaced lens if any.
+    fn insert(&mut self, label: &str, focal_length: u32) -> Option<u32> {
+        let previous = self.get(label);
+        if let Some(&(b, slot)) = self.index.get(label) {
+            if let Some(lens) = self.lens_box(b).slots[slot].as_mut() {
+                lens.1 = focal_length;
             }
+        } else {
+            let b = self.hasher.hash(label);
+            let lens_box = self.boxes.entry(b).or_default();
+            self.index
+                .insert(label.to_string(), (b, lens_box.slots.len()));
+            lens_box.slots.push(Some((label.to_string(), focal_length)));
+            lens_box.len += 1;
         }
-        unreachable!()
-    });
-    boxes.iter().enumerate().fold(0, |acc, (idx, entry)| {
-        let res = entry
-            .into_iter()
-            .enumerate()
-            .fold(0, |to_add, (i, (_, l))| {
-                to_add + ((i + 1) * (idx + 1) * *l as usize)
+        previous
+    }
+
+    fn remove(&mut self, label: &str) -> Option<u32> {
+        let (b, slot) = self.index.remove(label)?;
+        let lens_box = self.lens_box(b);
+        let (_, focal_length) = lens_box.slots[slot].take()?;
+        lens_box.len -= 1;
+        if lens_box.len == 0 {
+            self.boxes.remove(&b);
+        } else if lens_box.slots.len() > 2 * lens_box.len + 8 {
+            self.compact(b);
+        }
+        Some(focal_length)
+    }
+
+    fn lens_box(&mut self, b: u64) -> &mut LensBox {
+        self.boxes.get_mut(&b).expect("Should hold an indexed lens")
+    }
+
+    /// Drops the empty slots of a box, amortized over the removals that created them.
+    fn compact(&mut self, b: u64) {
+        let lens_box = self.boxes.get_mut(&b).expect("Should hold an indexed lens");
+        lens_box.slots.retain(|slot| slot.is_some());
+        lens_box.slots.iter().enumerate().for_each(|(slot, lens)| {
+            if let Some((label, _)) = lens {
+                self.index.insert(label.clone(), (b, slot));
+            }
+        });
+    }
+
+    fn get(&self, label: &str) -> Option<u32> {
+        let (b, slot) = self.index.get(label)?;
+        self.boxes.get(b)?.slots[*slot].as_ref().map(|(_, l)| *l)
+    }
+
+    fn apply(&mut self, step: &Step) {
+        match step {
+            Step::Insert {
+                label,
+                focal_length,
+            } => {
+                self.insert(label, *focal_length);
+            }
+            Step::Remove { label } => {
+                self.remove(label);
+            }
+        }
+    }
+
+    /// Every lens as `(box, label, focal length)`, box by box in insertion order.
+    fn iter(&self) -> impl Iterator<Item = (u64, &str, u32)> {
+        self.boxes.iter().flat_map(|(b, lens_box)| {
+            lens_box
+                .slots
+                .iter()
+                .flatten()
+                .map(move |(label, focal_length)| (*b, label.as_str(), *focal_length))
+        })
+    }
+
+    fn focusing_power(&self) -> usize {
+        let mut slot_in_box = 0;
+        let mut current_box = 0;
+        self.iter().fold(0, |acc, (b, _, focal_length)| {
+            let b = b as usize;
+            if b != current_box {
+                current_box = b;
+                slot_in_box = 0;
+            }
+            slot_in_box += 1;
+            acc + (b + 1) * slot_in_box * focal_length as usize
+        })
+    }
+}
+
+impl Display for LensBoxes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.boxes
+            .iter()
+            .filter(|(_, lens_box)| lens_box.len > 0)
+            .try_for_each(|(b, lens_box)| {
+                write!(f, "Box {b}:")?;
+                lens_box
+                    .slots
+                    .iter()
+                    .flatten()
+                    .try_for_each(|(label, focal_length)| write!(f, " [{label} {focal_length}]"))?;
+                writeln!(f)
+            })
+    }
+}
+
+#[derive(Debug)]
+struct CollisionReport {
+    /// Number of distinct labels hashed in each non empty bucket.
+    pub(crate) buckets: HashMap<u64, usize>,
+    pub(crate) nbr_buckets: u64,
+    pub(crate) nbr_labels: usize,
+    /// Labels landing in a bucket already holding another one.
+    pub(crate) nbr_collisions: usize,
+    /// `(bucket, number of labels)` of the most crowded bucket.
+    pub(crate) longest_chain: (u64, usize),
+}
+
+impl CollisionReport {
+    fn new<'a>(hasher: &LabelHasher, labels: impl Iterator<Item = &'a str>) -> Self {
+        let mut buckets = HashMap::new();
+        let mut seen = HashSet::new();
+        labels
+            .filter(|label| seen.insert(*label))
+            .for_each(|label| {
+                *buckets.entry(hasher.hash(label)).or_insert(0) += 1;
             });
-        acc + res
-    })
+        let nbr_labels = seen.len();
+        let longest_chain = buckets
+            .iter()
+            .map(|(bucket, nbr)| (*bucket, *nbr))
+            .max_by_key(|(bucket, nbr)| (*nbr, Reverse(*bucket)))
+            .unwrap_or_default();
+        Self {
+            nbr_collisions: nbr_labels - buckets.len(),
+            buckets,
+            nbr_buckets: hasher.modulus,
+            nbr_labels,
+            longest_chain,
+        }
+    }
+}
+
+impl Display for CollisionReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "labels : {}", self.nbr_labels)?;
+        writeln!(
+            f,
+            "buckets used : {} / {}",
+            self.buckets.len(),
+            self.nbr_buckets
+        )?;
+        writeln!(f, "collisions : {}", self.nbr_collisions)?;
+        writeln!(
+            f,
+            "longest chain : {} labels in bucket {}",
+            self.longest_chain.1, self.longest_chain.0
+        )?;
+        // Histogram of the bucket sizes : how many buckets hold 0, 1, 2... labels.
+        let mut distribution = vec![0; self.longest_chain.1 + 1];
+        distribution[0] = self.nbr_buckets - self.buckets.len() as u64;
+        self.buckets
+            .values()
+            .for_each(|nbr| distribution[*nbr] += 1);
+        distribution
+            .iter()
+            .enumerate()
+            .try_for_each(|(size, nbr)| writeln!(f, "  {size} labels : {nbr} buckets"))
+    }
+}
+
+fn parse_steps(file: &str) -> Vec<Step<'_>> {
+    file.split(',')
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| Step::try_from(s).expect("Should be a valid step"))
+        .collect()
+}
+
+pub(crate) fn eval_file_2(file: &str) -> usize {
+    let mut boxes = LensBoxes::default();
+    parse_steps(file).iter().for_each(|step| boxes.apply(step));
+    boxes.focusing_power()
 }
 pub(crate) fn print_sol_1(file: &str) {
     print!("res : {}", eval_file(file));
@@ -81,10 +296,29 @@ pub(crate) fn print_sol_1(file: &str) {
 pub(crate) fn print_sol_2(file: &str) {
     print!("res : {}", eval_file_2(file));
 }
+pub(crate) fn print_analysis(file: &str, hasher: LabelHasher) {
+    let steps = parse_steps(file);
+    let labels = steps.iter().map(|step| match step {
+        Step::Insert { label, .. } | Step::Remove { label } => *label,
+    });
+    print!("{}", CollisionReport::new(&hasher, labels));
+}
+pub(crate) fn print_trace(file: &str) {
+    let mut boxes = LensBoxes::default();
+    file.split(',')
+        .filter(|s| !s.trim().is_empty())
+        .for_each(|s| {
+            boxes.apply(&Step::try_from(s).expect("Should be a valid step"));
+            println!("After \"{}\":\n{boxes}", s.trim());
+        });
+    print!("res : {}", boxes.focusing_power());
+}
 
 #[cfg(test)]
 mod tests {
-    use super::{eval_file, eval_file_2};
+    use super::{
+        eval_file, eval_file_2, parse_steps, CollisionReport, LabelHasher, LensBoxes, Step,
+    };
 
     fn data() -> &'static str {
         r#"rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7"#
@@ -97,4 +331,90 @@ mod tests {
     fn test_1() {
         assert_eq!(145, eval_file_2(data()));
     }
+    #[test]
+    fn test_2() {
+        assert_eq!(
+            Ok(Step::Insert {
+                label: "rn",
+                focal_length: 1
+            }),
+            Step::try_from("rn=1")
+        );
+        assert_eq!(Ok(Step::Remove { label: "cm" }), Step::try_from("cm-\n"));
+        assert!(Step::try_from("cm").is_err());
+        assert!(Step::try_from("cm=x").is_err());
+    }
+    #[test]
+    fn test_3() {
+        let mut boxes = LensBoxes::default();
+        parse_steps(data())
+            .iter()
+            .for_each(|step| boxes.apply(step));
+        assert_eq!(
+            "Box 0: [rn 1] [cm 2]\nBox 3: [ot 7] [ab 5] [pc 6]\n",
+            boxes.to_string()
+        );
+        assert_eq!(Some(7), boxes.get("ot"));
+        assert_eq!(None, boxes.get("qp"));
+
+        assert_eq!(Some(7), boxes.insert("ot", 8));
+        (0..100).for_each(|i| assert_eq!(None, boxes.insert(&format!("ot{i}"), i)));
+        (0..99).for_each(|i| assert_eq!(Some(i), boxes.remove(&format!("ot{i}"))));
+        assert_eq!(Some(99), boxes.get("ot99"));
+        assert_eq!(
+            vec![(0, "rn", 1), (0, "cm", 2)],
+            boxes.iter().filter(|(b, _, _)| *b == 0).collect::<Vec<_>>()
+        );
+    }
+    #[test]
+    fn test_4() {
+        let hasher = LabelHasher::default();
+        assert_eq!(52, hasher.hash("HASH"));
+        let hasher = LabelHasher {
+            multiplier: 31,
+            modulus: 7,
+            seed: 3,
+        };
+        assert_eq!(((3 + 72) * 31) % 7, hasher.hash("H"));
+
+        let mut boxes = LensBoxes::new(hasher);
+        parse_steps(data())
+            .iter()
+            .for_each(|step| boxes.apply(step));
+        assert!(boxes.boxes.keys().all(|b| *b < 7));
+        assert_eq!(Some(6), boxes.get("pc"));
+
+        // Neither the hash nor the boxes depend on the modulus fitting in memory.
+        let hasher = LabelHasher {
+            multiplier: u64::MAX,
+            modulus: u64::MAX - 1,
+            seed: 0,
+        };
+        assert_eq!(72, hasher.hash("H"));
+        let hasher = LabelHasher {
+            multiplier: u64::MAX,
+            modulus: u64::MAX - 1,
+            seed: u64::MAX,
+        };
+        assert_eq!(1, hasher.hash(""));
+        assert_eq!(73, hasher.hash("H"));
+        let mut boxes = LensBoxes::new(hasher);
+        parse_steps(data())
+            .iter()
+            .for_each(|step| boxes.apply(step));
+        assert_eq!(Some(6), boxes.get("pc"));
+        let labels = ["rn", "cm", "qp"];
+        let report = CollisionReport::new(&hasher, labels.into_iter());
+        assert_eq!(0, report.nbr_collisions);
+        assert!(report.to_string().contains("buckets used : 3 / "));
+    }
+    #[test]
+    fn test_5() {
+        let labels = ["rn", "cm", "qp", "cm", "pc", "ot", "ab"];
+        let report = CollisionReport::new(&LabelHasher::default(), labels.into_iter());
+        assert_eq!(6, report.nbr_labels);
+        // rn and cm share box 0, pc, ot and ab all land in box 3.
+        assert_eq!(3, report.nbr_collisions);
+        assert_eq!((3, 3), report.longest_chain);
+    }
 }
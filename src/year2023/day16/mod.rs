@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::{thread, time::Duration};
 
 #[derive(Debug)]
 enum MirroDirection {
@@ -183,217 +183,418 @@ impl Coord {
 
 #[derive(Debug)]
 struct Map {
-    pub(crate) map: HashMap<Coord, Obstacle>,
+    /// Obstacles row by row, `None` for empty space.
+    pub(crate) map: Vec<Option<Obstacle>>,
+    /// Index of the last row
     pub(crate) height: usize,
+    /// Index of the last column
     pub(crate) width: usize,
 }
 
 impl From<&str> for Map {
     fn from(value: &str) -> Self {
-        let mut ret = HashMap::new();
-        let mut height = 0;
-        let mut width = 0;
-        value
+        let lines = value
             .lines()
             .map(|l| l.trim())
             .filter(|l| !l.is_empty())
-            .enumerate()
-            .for_each(|(x, l)| {
-                height = height.max(x);
-
-                l.chars().enumerate().for_each(|(y, c)| {
-                    match c {
-                        '-' => {
-                            ret.insert(
-                                Coord { x, y },
-                                Obstacle::Splitter(SplitterDirection::Horizontal),
-                            );
-                        }
-                        '|' => {
-                            ret.insert(
-                                Coord { x, y },
-                                Obstacle::Splitter(SplitterDirection::Vertical),
-                            );
-                        }
-                        '/' => {
-                            ret.insert(Coord { x, y }, Obstacle::Mirror(MirroDirection::Slash));
-                        }
-                        '\\' => {
-                            ret.insert(Coord { x, y }, Obstacle::Mirror(MirroDirection::Backslash));
-                        }
-                        '.' => (),
-                        _ => unreachable!(),
-                    };
-                    if x == 0 {
-                        width = width.max(y);
-                    }
+            .collect::<Vec<_>>();
+        let map = lines
+            .iter()
+            .flat_map(|l| {
+                l.chars().map(|c| match c {
+                    '-' => Some(Obstacle::Splitter(SplitterDirection::Horizontal)),
+                    '|' => Some(Obstacle::Splitter(SplitterDirection::Vertical)),
+                    '/' => Some(Obstacle::Mirror(MirroDirection::Slash)),
+                    '\\' => Some(Obstacle::Mirror(MirroDirection::Backslash)),
+                    '.' => None,
+                    _ => unreachable!(),
                 })
-            });
+            })
+            .collect();
 
         Self {
-            map: ret,
-            height,
-            width,
+            map,
+            height: lines.len() - 1,
+            width: lines[0].len() - 1,
         }
     }
 }
 
-impl Map {
-    fn resolve_ray(&self, seen: &mut HashSet<Ray>, ray: Ray, coords: &mut HashSet<Coord>) {
-        let mut pos = ray.starting_pos.clone();
-        let dir = ray.direction.clone();
-        loop {
-            if let Some(p) = pos.add_direction(&dir, self.height, self.width) {
-                coords.insert(p.clone());
-                if let Some(obs) = self.map.get(&p) {
-                    ray.encounter_obstacle(p, obs).into_iter().for_each(|r| {
-                        if !seen.contains(&r) {
-                            seen.insert(r.clone());
-                            self.resolve_ray(seen, r, coords)
-                        }
-                    });
-                    break;
-                }
-                pos = p;
-            } else {
-                break;
-            }
+impl Obstacle {
+    fn to_char(&self) -> char {
+        match self {
+            Obstacle::Mirror(MirroDirection::Slash) => '/',
+            Obstacle::Mirror(MirroDirection::Backslash) => '\\',
+            Obstacle::Splitter(SplitterDirection::Horizontal) => '-',
+            Obstacle::Splitter(SplitterDirection::Vertical) => '|',
         }
     }
 }
 
-pub(crate) fn eval_file(file: &str) -> usize {
-    let map: Map = file.into();
-    let ray = Ray::new(Direction::WestToEast, Default::default());
+impl Direction {
+    fn index(&self) -> usize {
+        match self {
+            Direction::NorthToSouth => 0,
+            Direction::SouthToNorth => 1,
+            Direction::EastToWest => 2,
+            Direction::WestToEast => 3,
+        }
+    }
+}
+
+impl Map {
+    fn cols(&self) -> usize {
+        self.width + 1
+    }
+
+    fn rows(&self) -> usize {
+        self.height + 1
+    }
 
-    let mut coords = HashSet::new();
-    let mut seen = HashSet::new();
+    fn get(&self, coord: &Coord) -> Option<&Obstacle> {
+        self.map[coord.x * self.cols() + coord.y].as_ref()
+    }
 
-    let starting_coord = Coord::default();
-    coords.insert(Coord::default());
-    seen.insert(ray.clone());
+    /// Every ray entering the contraption from one of its edges.
+    fn edge_entries(&self) -> Vec<Ray> {
+        let from_sides = (0..self.rows()).flat_map(|x| {
+            [
+                Ray::new(Direction::WestToEast, Coord::new(x, 0)),
+                Ray::new(Direction::EastToWest, Coord::new(x, self.width)),
+            ]
+        });
+        let from_ends = (0..self.cols()).flat_map(|y| {
+            [
+                Ray::new(Direction::NorthToSouth, Coord::new(0, y)),
+                Ray::new(Direction::SouthToNorth, Coord::new(self.height, y)),
+            ]
+        });
+        from_sides.chain(from_ends).collect()
+    }
 
-    if let Some(obs) = map.map.get(&starting_coord) {
-        ray.encounter_obstacle(starting_coord, obs)
+    /// Directions crossed on each tile by a beam entering the `entry.starting_pos`
+    /// tile, one bit per direction so the walk stops as soon as it would repeat
+    /// itself. Beams advance one tile at a time, `on_frame` seeing each step.
+    fn propagate(&self, entry: Ray, mut on_frame: impl FnMut(&[u8])) -> Vec<u8> {
+        let mut crossed = vec![0u8; self.rows() * self.cols()];
+        let mut rays = vec![entry];
+        while !rays.is_empty() {
+            let mut next_rays = vec![];
+            for ray in rays {
+                let tile = ray.starting_pos.x * self.cols() + ray.starting_pos.y;
+                let bit = 1 << ray.direction.index();
+                if crossed[tile] & bit != 0 {
+                    continue;
+                }
+                crossed[tile] |= bit;
+                let position = ray.starting_pos.clone();
+                let outgoing = match self.get(&position) {
+                    Some(obs) => ray.encounter_obstacle(position, obs),
+                    None => vec![ray],
+                };
+                next_rays.extend(outgoing.into_iter().filter_map(|r| {
+                    r.starting_pos
+                        .add_direction(&r.direction, self.height, self.width)
+                        .map(|p| Ray::new(r.direction, p))
+                }));
+            }
+            on_frame(&crossed);
+            rays = next_rays;
+        }
+        crossed
+    }
+
+    /// Number of tiles energized by a beam entering the `entry.starting_pos` tile.
+    fn energize(&self, entry: Ray) -> usize {
+        self.propagate(entry, |_| ())
             .into_iter()
-            .for_each(|r| {
-                if !seen.contains(&r) {
-                    seen.insert(r.clone());
-                    map.resolve_ray(&mut seen, r, &mut coords)
+            .filter(|c| *c != 0)
+            .count()
+    }
+
+    /// Contraption as in the puzzle statement : the beam direction on empty tiles,
+    /// or the number of beams when several cross it. `highlight` is shown reversed.
+    fn render_beams(&self, crossed: &[u8], highlight: Option<&Coord>) -> String {
+        let mut ret = String::new();
+        (0..self.rows()).for_each(|x| {
+            (0..self.cols()).for_each(|y| {
+                let tile = x * self.cols() + y;
+                let c = match (&self.map[tile], crossed[tile].count_ones()) {
+                    (Some(obs), _) => obs.to_char(),
+                    (None, 0) => '.',
+                    (None, 1) => match crossed[tile].trailing_zeros() {
+                        0 => 'v',
+                        1 => '^',
+                        2 => '<',
+                        _ => '>',
+                    },
+                    (None, nbr) => char::from_digit(nbr, 10).expect("At most 4 beams"),
+                };
+                if highlight == Some(&Coord::new(x, y)) {
+                    ret.push_str(&format!("\x1b[7m{c}\x1b[0m"));
+                } else {
+                    ret.push(c);
                 }
             });
-    } else {
-        map.resolve_ray(&mut seen, ray, &mut coords);
+            ret.push('\n');
+        });
+        ret
     }
 
-    coords.len()
-}
+    fn render_energized(&self, crossed: &[u8]) -> String {
+        let mut ret = String::new();
+        crossed.chunks(self.cols()).for_each(|line| {
+            line.iter()
+                .for_each(|c| ret.push(if *c != 0 { '#' } else { '.' }));
+            ret.push('\n');
+        });
+        ret
+    }
 
-pub(crate) fn eval_file_2(file: &str) -> usize {
-    let map: Map = file.into();
-    let mut max_len = 0;
-
-    for x in 0..(map.height) {
-        {
-            let starting_coord = Coord::new(x, map.width - 1);
-            let ray = Ray::new(Direction::EastToWest, starting_coord.clone());
-
-            let mut coords = HashSet::new();
-            let mut seen = HashSet::new();
-
-            coords.insert(starting_coord.clone());
-            seen.insert(ray.clone());
-
-            if let Some(obs) = map.map.get(&starting_coord) {
-                ray.encounter_obstacle(starting_coord, obs)
-                    .into_iter()
-                    .for_each(|r| {
-                        if !seen.contains(&r) {
-                            seen.insert(r.clone());
-                            map.resolve_ray(&mut seen, r, &mut coords)
-                        }
-                    });
-            } else {
-                map.resolve_ray(&mut seen, ray, &mut coords);
+    /// Prints every step of the beams `delay` apart, or only the final state without delay.
+    fn visualize(&self, entry: Ray, delay: Option<Duration>) {
+        let highlight = entry.starting_pos.clone();
+        let crossed = self.propagate(entry, |crossed| {
+            if let Some(delay) = delay {
+                print!(
+                    "\x1b[2J\x1b[H{}",
+                    self.render_beams(crossed, Some(&highlight))
+                );
+                thread::sleep(delay);
             }
-            max_len = max_len.max(coords.len())
+        });
+        if delay.is_none() {
+            println!("{}", self.render_beams(&crossed, Some(&highlight)));
         }
-        {
-            let starting_coord = Coord::new(x, 0);
-            let ray = Ray::new(Direction::WestToEast, starting_coord.clone());
-
-            let mut coords = HashSet::new();
-            let mut seen = HashSet::new();
-
-            coords.insert(starting_coord.clone());
-            seen.insert(ray.clone());
-
-            if let Some(obs) = map.map.get(&starting_coord) {
-                ray.encounter_obstacle(starting_coord, obs)
-                    .into_iter()
-                    .for_each(|r| {
-                        if !seen.contains(&r) {
-                            seen.insert(r.clone());
-                            map.resolve_ray(&mut seen, r, &mut coords)
-                        }
-                    });
-            } else {
-                map.resolve_ray(&mut seen, ray, &mut coords);
+        println!("{}", self.render_energized(&crossed));
+    }
+}
+
+/// The contraption compiled into a graph whose nodes are the beam segments
+/// leaving an obstacle in a given direction, up to the next obstacle or edge.
+/// Nodes are condensed into strongly connected components, each knowing every
+/// tile energized from it, so an entry only has to union a few of those sets.
+struct BeamGraph {
+    /// Component of the node `obstacle * 4 + direction`.
+    node_comp: Vec<usize>,
+    /// Tiles energized from each component, as bitsets.
+    comp_tiles: Vec<Vec<u64>>,
+    /// Index of the obstacle on each tile, if any.
+    obstacle_idx: Vec<Option<usize>>,
+    /// Length of the tile bitsets.
+    words: usize,
+}
+
+impl Map {
+    /// Tiles crossed when leaving `from` towards `dir`, and the obstacle stopping the beam.
+    fn segment(&self, from: &Coord, dir: &Direction) -> (Vec<usize>, Option<Coord>) {
+        let mut tiles = vec![];
+        let mut pos = from.clone();
+        while let Some(p) = pos.add_direction(dir, self.height, self.width) {
+            tiles.push(p.x * self.cols() + p.y);
+            if self.get(&p).is_some() {
+                return (tiles, Some(p));
             }
-            max_len = max_len.max(coords.len())
+            pos = p;
         }
+        (tiles, None)
     }
-    for y in 0..(map.width) {
-        {
-            let starting_coord = Coord::new(map.height - 1, y);
-            let ray = Ray::new(Direction::SouthToNorth, starting_coord.clone());
-
-            let mut coords = HashSet::new();
-            let mut seen = HashSet::new();
-
-            coords.insert(starting_coord.clone());
-            seen.insert(ray.clone());
-
-            if let Some(obs) = map.map.get(&starting_coord) {
-                ray.encounter_obstacle(starting_coord, obs)
-                    .into_iter()
-                    .for_each(|r| {
-                        if !seen.contains(&r) {
-                            seen.insert(r.clone());
-                            map.resolve_ray(&mut seen, r, &mut coords)
-                        }
-                    });
-            } else {
-                map.resolve_ray(&mut seen, ray, &mut coords);
+
+    /// Directions in which a beam coming in `dir` leaves the obstacle at `pos`.
+    fn outgoing(&self, pos: &Coord, dir: &Direction) -> Vec<Direction> {
+        let obs = self.get(pos).expect("Should be an obstacle");
+        Ray::new(dir.clone(), pos.clone())
+            .encounter_obstacle(pos.clone(), obs)
+            .into_iter()
+            .map(|r| r.direction)
+            .collect()
+    }
+}
+
+impl From<&Map> for BeamGraph {
+    fn from(map: &Map) -> Self {
+        let nbr_tiles = map.rows() * map.cols();
+        let words = nbr_tiles.div_ceil(64);
+        let obstacles = (0..nbr_tiles)
+            .filter(|t| map.map[*t].is_some())
+            .map(|t| Coord::new(t / map.cols(), t % map.cols()))
+            .collect::<Vec<_>>();
+        let mut obstacle_idx = vec![None; nbr_tiles];
+        obstacles
+            .iter()
+            .enumerate()
+            .for_each(|(i, c)| obstacle_idx[c.x * map.cols() + c.y] = Some(i));
+
+        let directions = [
+            Direction::NorthToSouth,
+            Direction::SouthToNorth,
+            Direction::EastToWest,
+            Direction::WestToEast,
+        ];
+        let (node_tiles, node_succ): (Vec<Vec<usize>>, Vec<Vec<usize>>) = obstacles
+            .iter()
+            .flat_map(|obstacle| directions.iter().map(move |dir| (obstacle, dir)))
+            .map(|(obstacle, dir)| {
+                let (tiles, hit) = map.segment(obstacle, dir);
+                let succ = hit
+                    .map(|h| {
+                        let idx = obstacle_idx[h.x * map.cols() + h.y].expect("Is an obstacle");
+                        map.outgoing(&h, dir)
+                            .iter()
+                            .map(|d| idx * 4 + d.index())
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                (tiles, succ)
+            })
+            .unzip();
+
+        // Iterative Tarjan : components are closed in reverse topological order,
+        // so the ones reachable from a new component already have their tile set.
+        let nbr_nodes = node_tiles.len();
+        let mut index = vec![usize::MAX; nbr_nodes];
+        let mut low = vec![0; nbr_nodes];
+        let mut on_stack = vec![false; nbr_nodes];
+        let mut stack = vec![];
+        let mut node_comp = vec![usize::MAX; nbr_nodes];
+        let mut comp_tiles: Vec<Vec<u64>> = vec![];
+        let mut counter = 0;
+        for root in 0..nbr_nodes {
+            if index[root] != usize::MAX {
+                continue;
             }
-            max_len = max_len.max(coords.len())
-        }
-        {
-            let starting_coord = Coord::new(0, y);
-            let ray = Ray::new(Direction::NorthToSouth, starting_coord.clone());
-
-            let mut coords = HashSet::new();
-            let mut seen = HashSet::new();
-
-            coords.insert(starting_coord.clone());
-            seen.insert(ray.clone());
-
-            if let Some(obs) = map.map.get(&starting_coord) {
-                ray.encounter_obstacle(starting_coord, obs)
-                    .into_iter()
-                    .for_each(|r| {
-                        if !seen.contains(&r) {
-                            seen.insert(r.clone());
-                            map.resolve_ray(&mut seen, r, &mut coords)
+            let mut work = vec![(root, 0)];
+            index[root] = counter;
+            low[root] = counter;
+            counter += 1;
+            stack.push(root);
+            on_stack[root] = true;
+            while let Some((v, next)) = work.last().copied() {
+                if let Some(w) = node_succ[v].get(next).copied() {
+                    work.last_mut().expect("Is present").1 += 1;
+                    if index[w] == usize::MAX {
+                        index[w] = counter;
+                        low[w] = counter;
+                        counter += 1;
+                        stack.push(w);
+                        on_stack[w] = true;
+                        work.push((w, 0));
+                    } else if on_stack[w] {
+                        low[v] = low[v].min(index[w]);
+                    }
+                    continue;
+                }
+                work.pop();
+                if let Some((u, _)) = work.last() {
+                    low[*u] = low[*u].min(low[v]);
+                }
+                if low[v] == index[v] {
+                    let comp = comp_tiles.len();
+                    let mut members = vec![];
+                    while let Some(w) = stack.pop() {
+                        on_stack[w] = false;
+                        node_comp[w] = comp;
+                        members.push(w);
+                        if w == v {
+                            break;
                         }
+                    }
+                    let mut tiles = vec![0u64; words];
+                    members.iter().for_each(|m| {
+                        node_tiles[*m]
+                            .iter()
+                            .for_each(|t| tiles[t / 64] |= 1 << (t % 64));
+                        node_succ[*m]
+                            .iter()
+                            .filter(|w| node_comp[**w] != comp)
+                            .for_each(|w| {
+                                tiles
+                                    .iter_mut()
+                                    .zip(&comp_tiles[node_comp[*w]])
+                                    .for_each(|(t, o)| *t |= o)
+                            });
                     });
-            } else {
-                map.resolve_ray(&mut seen, ray, &mut coords);
+                    comp_tiles.push(tiles);
+                }
             }
-            max_len = max_len.max(coords.len())
+        }
+
+        Self {
+            node_comp,
+            comp_tiles,
+            obstacle_idx,
+            words,
         }
     }
-    max_len
+}
+
+impl BeamGraph {
+    /// Same result as `Map::energize`, without walking the beams again.
+    fn energize(&self, map: &Map, entry: &Ray) -> usize {
+        let start = &entry.starting_pos;
+        let start_tile = start.x * map.cols() + start.y;
+        let (mut tiles, hit) = if map.get(start).is_some() {
+            (vec![], Some(start.clone()))
+        } else {
+            map.segment(start, &entry.direction)
+        };
+        tiles.push(start_tile);
+
+        let mut energized = vec![0u64; self.words];
+        if let Some(h) = hit {
+            let idx = self.obstacle_idx[h.x * map.cols() + h.y].expect("Is an obstacle");
+            map.outgoing(&h, &entry.direction).iter().for_each(|d| {
+                let comp = &self.comp_tiles[self.node_comp[idx * 4 + d.index()]];
+                energized.iter_mut().zip(comp).for_each(|(t, o)| *t |= o);
+            });
+        }
+        tiles
+            .iter()
+            .for_each(|t| energized[t / 64] |= 1 << (t % 64));
+        energized.iter().map(|t| t.count_ones() as usize).sum()
+    }
+}
+
+pub(crate) fn eval_file(file: &str) -> usize {
+    let map: Map = file.into();
+    map.energize(Ray::new(Direction::WestToEast, Default::default()))
+}
+
+pub(crate) fn eval_file_2(file: &str) -> usize {
+    let map: Map = file.into();
+    let graph = BeamGraph::from(&map);
+    map.edge_entries()
+        .iter()
+        .map(|entry| graph.energize(&map, entry))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Simulates every edge entry, split between threads, keeping the best of each.
+pub(crate) fn eval_file_2_simulated(file: &str) -> usize {
+    let map: Map = file.into();
+    let entries = map.edge_entries();
+    let nbr_threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = entries.len().div_ceil(nbr_threads).max(1);
+    thread::scope(|scope| {
+        entries
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let map = &map;
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|entry| map.energize(entry.clone()))
+                        .max()
+                        .unwrap_or(0)
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().expect("Thread should not panic"))
+            .max()
+            .unwrap_or(0)
+    })
 }
 pub(crate) fn print_sol_1(file: &str) {
     print!("res : {}", eval_file(file));
@@ -401,10 +602,32 @@ pub(crate) fn print_sol_1(file: &str) {
 pub(crate) fn print_sol_2(file: &str) {
     print!("res : {}", eval_file_2(file));
 }
+pub(crate) fn print_sol_2_simulated(file: &str) {
+    print!("res : {}", eval_file_2_simulated(file));
+}
+pub(crate) fn print_visualization(file: &str, delay: Option<u64>) {
+    let map: Map = file.into();
+    let entry = Ray::new(Direction::WestToEast, Default::default());
+    map.visualize(entry, delay.map(Duration::from_millis));
+}
+pub(crate) fn print_best_visualization(file: &str, delay: Option<u64>) {
+    let map: Map = file.into();
+    let graph = BeamGraph::from(&map);
+    let best = map
+        .edge_entries()
+        .into_iter()
+        .max_by_key(|entry| graph.energize(&map, entry))
+        .expect("Should have an entry");
+    let description = format!("best entry : {:?} {:?}", best.starting_pos, best.direction);
+    map.visualize(best, delay.map(Duration::from_millis));
+    print!("{description}");
+}
 
 #[cfg(test)]
 mod tests {
-    use super::{eval_file, eval_file_2};
+    use super::{
+        eval_file, eval_file_2, eval_file_2_simulated, BeamGraph, Coord, Direction, Map, Ray,
+    };
 
     fn data() -> &'static str {
         r#"
@@ -428,4 +651,58 @@ mod tests {
     fn test_1() {
         assert_eq!(51, eval_file_2(data()));
     }
+    #[test]
+    fn test_2() {
+        let map: Map = data().into();
+        assert_eq!(40, map.edge_entries().len());
+        assert_eq!(
+            51,
+            map.energize(Ray::new(Direction::NorthToSouth, Coord::new(0, 3)))
+        );
+    }
+    #[test]
+    fn test_3() {
+        assert_eq!(51, eval_file_2_simulated(data()));
+        let map: Map = data().into();
+        let graph = BeamGraph::from(&map);
+        map.edge_entries()
+            .iter()
+            .for_each(|entry| assert_eq!(map.energize(entry.clone()), graph.energize(&map, entry)));
+    }
+    #[test]
+    fn test_4() {
+        let map: Map = data().into();
+        let entry = Ray::new(Direction::WestToEast, Default::default());
+        let mut nbr_frames = 0;
+        let crossed = map.propagate(entry, |_| nbr_frames += 1);
+        assert!(nbr_frames > 1);
+        assert_eq!(
+            r#">|<<<\....
+|v-.\^....
+.v...|->>>
+.v...v^.|.
+.v...v^...
+.v...v^..\
+.v../2\\..
+<->-/vv|..
+.|<<<2-|.\
+.v//.|.v..
+"#,
+            map.render_beams(&crossed, None)
+        );
+        assert_eq!(
+            r#"######....
+.#...#....
+.#...#####
+.#...##...
+.#...##...
+.#...##...
+.#..####..
+########..
+.#######..
+.#...#.#..
+"#,
+            map.render_energized(&crossed)
+        );
+    }
 }
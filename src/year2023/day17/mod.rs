@@ -1,6 +1,6 @@
 use std::{collections::BinaryHeap, ops::Deref};
 
-#[derive(Hash, PartialEq, Eq, Debug, Clone)]
+#[derive(Hash, PartialEq, Eq, Debug, Clone, Copy)]
 enum Dir {
     E,
     N,
@@ -17,10 +17,25 @@ impl Dir {
             Dir::S => 3,
         }
     }
-    fn is_opp(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Dir::N, Dir::S) | (Dir::E, Dir::W) | (Dir::W, Dir::E) | (Dir::S, Dir::N) => true,
-            _ => false,
+    fn from_index(idx: usize) -> Self {
+        [Dir::E, Dir::N, Dir::W, Dir::S][idx]
+    }
+    /// Quarter turn to the left, as `E`, `N`, `W`, `S` go counterclockwise.
+    fn left(&self) -> Self {
+        Self::from_index((self.index() + 1) % 4)
+    }
+    fn right(&self) -> Self {
+        Self::from_index((self.index() + 3) % 4)
+    }
+    fn opposite(&self) -> Self {
+        Self::from_index((self.index() + 2) % 4)
+    }
+    fn arrow(&self) -> char {
+        match self {
+            Dir::E => '>',
+            Dir::N => '^',
+            Dir::W => '<',
+            Dir::S => 'v',
         }
     }
 }
@@ -60,106 +75,262 @@ impl Deref for Map {
 struct Step {
     pub(crate) coord: usize,
     pub(crate) heat_loss: u32,
+    /// Heat loss plus the heuristic estimate of the remaining one.
+    pub(crate) priority: u32,
     pub(crate) movement: (Dir, usize),
-    pub(crate) past: Vec<usize>,
 }
 
 impl PartialOrd for Step {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        other.heat_loss.partial_cmp(&self.heat_loss)
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for Step {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        other.heat_loss.cmp(&self.heat_loss)
+        other.priority.cmp(&self.priority)
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Search {
+    Dijkstra,
+    /// Guided by the manhattan distance to the goal times the smallest heat loss of a block.
+    AStar,
+}
+
+/// Which ways the crucible may leave a block, compared to the way it entered it.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Turns {
+    pub(crate) left: bool,
+    pub(crate) right: bool,
+    pub(crate) u_turn: bool,
+}
+
+impl Default for Turns {
+    fn default() -> Self {
+        Self {
+            left: true,
+            right: true,
+            u_turn: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Constraints {
+    /// Moves to do in a direction before turning or stopping.
+    pub(crate) min_run: usize,
+    /// Moves allowed in a direction before having to turn.
+    pub(crate) max_run: usize,
+    /// `(row, col)` of the starting block.
+    pub(crate) start: (usize, usize),
+    /// `(row, col)` of the goal, the bottom-right block if `None`.
+    pub(crate) goal: Option<(usize, usize)>,
+    pub(crate) turns: Turns,
+    pub(crate) search: Search,
+}
+
+impl Constraints {
+    fn crucible() -> Self {
+        Self {
+            min_run: 1,
+            max_run: 3,
+            start: (0, 0),
+            goal: None,
+            turns: Turns::default(),
+            search: Search::AStar,
+        }
+    }
+
+    fn ultra_crucible() -> Self {
+        Self {
+            min_run: 4,
+            max_run: 10,
+            ..Self::crucible()
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PathResult {
+    pub(crate) heat_loss: u32,
+    /// `(row, col)` of every block crossed, starting with the starting one.
+    pub(crate) coords: Vec<(usize, usize)>,
+    /// Move leading to each block but the first one.
+    pub(crate) moves: Vec<Dir>,
+    /// Number of states popped from the queue, to compare searches.
+    pub(crate) expanded: usize,
+}
+
 impl Map {
-    fn solve<const MIN: usize, const MAX: usize>(&self) -> u32 {
+    fn neighbour(&self, coord: usize, dir: Dir) -> Option<usize> {
+        let (x, y) = (coord / self.cols, coord % self.cols);
+        match dir {
+            Dir::E => (y + 1 < self.cols).then_some(coord + 1),
+            Dir::N => (x > 0).then(|| coord - self.cols),
+            Dir::W => (y > 0).then(|| coord - 1),
+            Dir::S => (x + 1 < self.rows).then_some(coord + self.cols),
+        }
+    }
+
+    /// States are indexed by block, direction and number of moves already done
+    /// in that direction minus one, with `max_run` such counts per direction.
+    fn solve(&self, constraints: &Constraints) -> Option<PathResult> {
+        let Constraints {
+            min_run,
+            max_run,
+            start,
+            goal,
+            turns,
+            search,
+        } = constraints.clone();
         let size = self.len();
-        let mut seen = vec![(false, u32::MAX); size * 4 * MAX as usize];
-        let mut queue = BinaryHeap::from([
-            Step {
-                coord: self.rows,
-                heat_loss: self.map[self.rows],
-                movement: (Dir::S, 0),
-                past: vec![self.rows],
-            },
-            Step {
-                coord: 1,
-                heat_loss: self.map[1],
-                movement: (Dir::E, 0),
-                past: vec![1],
-            },
-        ]);
+        let start = start.0 * self.cols + start.1;
+        let goal = goal.map_or(size - 1, |(x, y)| x * self.cols + y);
+        if start >= size || goal >= size || max_run == 0 || min_run > max_run {
+            return None;
+        }
+        let min_heat_loss = self.iter().copied().min().unwrap_or(0);
+        let heuristic = |coord: usize| match search {
+            Search::Dijkstra => 0,
+            Search::AStar => {
+                let dst = (coord / self.cols).abs_diff(goal / self.cols)
+                    + (coord % self.cols).abs_diff(goal % self.cols);
+                dst as u32 * min_heat_loss
+            }
+        };
+        let state_idx =
+            |coord: usize, (dir, run): (Dir, usize)| (coord * 4 + dir.index()) * max_run + run - 1;
+
+        let mut heat_losses = vec![u32::MAX; size * 4 * max_run];
+        let mut parents = vec![None; size * 4 * max_run];
+        let mut queue = BinaryHeap::new();
+        [Dir::E, Dir::N, Dir::W, Dir::S]
+            .into_iter()
+            .filter_map(|dir| self.neighbour(start, dir).map(|coord| (coord, dir)))
+            .for_each(|(coord, dir)| {
+                heat_losses[state_idx(coord, (dir, 1))] = self.map[coord];
+                queue.push(Step {
+                    coord,
+                    heat_loss: self.map[coord],
+                    priority: self.map[coord] + heuristic(coord),
+                    movement: (dir, 1),
+                });
+            });
+        let mut expanded = 0;
         while let Some(step) = queue.pop() {
-            if step.coord == size - 1 && step.movement.1 >= MIN {
-                return step.heat_loss;
+            let state = state_idx(step.coord, step.movement);
+            if step.heat_loss > heat_losses[state] {
+                continue;
+            }
+            expanded += 1;
+            let (current_dir, run) = step.movement;
+            if step.coord == goal && run >= min_run {
+                let mut path = self.build_path(start, state, max_run, &parents);
+                path.heat_loss = step.heat_loss;
+                path.expanded = expanded;
+                return Some(path);
+            }
+            let can_turn = run >= min_run;
+            let next_dirs = [
+                (current_dir, run < max_run),
+                (current_dir.left(), can_turn && turns.left),
+                (current_dir.right(), can_turn && turns.right),
+                (current_dir.opposite(), can_turn && turns.u_turn),
+            ];
+            for (dir, allowed) in next_dirs {
+                let Some(coord) = self.neighbour(step.coord, dir).filter(|_| allowed) else {
+                    continue;
+                };
+                let movement = if dir == current_dir {
+                    (dir, run + 1)
+                } else {
+                    (dir, 1)
+                };
+                let heat_loss = step.heat_loss + self.map[coord];
+                let next = state_idx(coord, movement);
+                if heat_loss < heat_losses[next] {
+                    heat_losses[next] = heat_loss;
+                    parents[next] = Some(state);
+                    queue.push(Step {
+                        coord,
+                        heat_loss,
+                        priority: heat_loss + heuristic(coord),
+                        movement,
+                    });
+                }
             }
-            queue.extend(
-                [Dir::N, Dir::E, Dir::W, Dir::S]
-                    .into_iter()
-                    .filter_map(|dir| {
-                        if step.movement.0.is_opp(&dir)
-                            || (dir == step.movement.0 && step.movement.1 + 1 >= MAX)
-                            || (dir != step.movement.0 && step.movement.1 < MIN)
-                            || match dir {
-                                Dir::E => step.coord % self.cols == self.cols - 1,
-                                Dir::N => step.coord < self.cols,
-                                Dir::W => step.coord % self.cols == 0,
-                                Dir::S => step.coord / self.cols == self.rows - 1,
-                            }
-                        {
-                            return None;
-                        }
-                        let movement = if dir == step.movement.0 {
-                            (dir.clone(), step.movement.1 + 1)
-                        } else {
-                            (dir.clone(), 0)
-                        };
-                        let coord = match dir {
-                            Dir::E => step.coord + 1,
-                            Dir::N => step.coord - self.cols,
-                            Dir::W => step.coord - 1,
-                            Dir::S => step.coord + self.cols,
-                        };
-                        let heat_loss = step.heat_loss + self.map[coord];
-                        let idx = (coord) * 4 * MAX + movement.0.index() * MAX + (movement.1);
-                        if !seen[idx as usize].0 {
-                            seen[idx as usize] = (true, heat_loss);
-                            let mut past = step.past.clone();
-                            past.push(coord);
-                            Some(Step {
-                                coord,
-                                heat_loss,
-                                movement,
-                                past,
-                            })
-                        } else {
-                            None
-                        }
-                    }),
-            );
         }
-        0
-        // *seen[(size - 1) * MAX * 4 + MIN..]
-        //     .iter()
-        //     .map(|(_, v)| v)
-        //     .min()
-        //     .unwrap()
+        None
+    }
+
+    /// Follows the parent pointers back from the `state` reaching the goal.
+    fn build_path(
+        &self,
+        start: usize,
+        state: usize,
+        max_run: usize,
+        parents: &[Option<usize>],
+    ) -> PathResult {
+        let mut states = vec![state];
+        while let Some(parent) = parents[*states.last().expect("Is present")] {
+            states.push(parent);
+        }
+        states.reverse();
+        let coords = std::iter::once(start)
+            .chain(states.iter().map(|s| s / (4 * max_run)))
+            .map(|coord| (coord / self.cols, coord % self.cols))
+            .collect();
+        let moves = states
+            .iter()
+            .map(|s| Dir::from_index(s / max_run % 4))
+            .collect();
+        PathResult {
+            heat_loss: 0,
+            coords,
+            moves,
+            expanded: 0,
+        }
+    }
+
+    /// The map with the path drawn with arrows, as in the puzzle statement.
+    fn render_path(&self, path: &PathResult) -> String {
+        let mut grid = self
+            .chunks(self.cols)
+            .map(|line| {
+                line.iter()
+                    .map(|v| char::from_digit(*v, 10).expect("Is a digit"))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        path.coords
+            .iter()
+            .skip(1)
+            .zip(&path.moves)
+            .for_each(|((x, y), dir)| grid[*x][*y] = dir.arrow());
+        grid.into_iter()
+            .map(|line| {
+                line.into_iter()
+                    .chain(std::iter::once('\n'))
+                    .collect::<String>()
+            })
+            .collect()
     }
 }
 
 pub(crate) fn eval_file(file: &str) -> u32 {
     let map: Map = file.into();
-    map.solve::<0, 3>()
+    map.solve(&Constraints::crucible())
+        .expect("Should reach the bottom-right block")
+        .heat_loss
 }
 
 pub(crate) fn eval_file_2(file: &str) -> u32 {
     let map: Map = file.into();
-    map.solve::<3, 10>()
+    map.solve(&Constraints::ultra_crucible())
+        .expect("Should reach the bottom-right block")
+        .heat_loss
 }
 pub(crate) fn print_sol_1(file: &str) {
     print!("res : {}", eval_file(file));
@@ -167,10 +338,32 @@ pub(crate) fn print_sol_1(file: &str) {
 pub(crate) fn print_sol_2(file: &str) {
     print!("res : {}", eval_file_2(file));
 }
+fn print_path(map: &Map, path: Option<PathResult>) {
+    match path {
+        Some(path) => print!("{}res : {}", map.render_path(&path), path.heat_loss),
+        None => print!("no path"),
+    }
+}
+pub(crate) fn print_path_1(file: &str) {
+    let map: Map = file.into();
+    print_path(&map, map.solve(&Constraints::crucible()));
+}
+pub(crate) fn print_path_2(file: &str) {
+    let map: Map = file.into();
+    print_path(&map, map.solve(&Constraints::ultra_crucible()));
+}
+pub(crate) fn print_custom_path(file: &str, constraints: &Constraints) {
+    let map: Map = file.into();
+    let path = map.solve(constraints);
+    if let Some(path) = &path {
+        println!("expanded states : {}", path.expanded);
+    }
+    print_path(&map, path);
+}
 
 #[cfg(test)]
 mod tests {
-    use super::{eval_file, eval_file_2};
+    use super::{eval_file, eval_file_2, Constraints, Dir, Map, Search, Turns};
 
     fn data() -> &'static str {
         r#"
@@ -209,4 +402,108 @@ mod tests {
     fn test_2() {
         assert_eq!(71, eval_file_2(data_1()));
     }
+    #[test]
+    fn test_3() {
+        let map: Map = data().into();
+        let path = map
+            .solve(&Constraints::crucible())
+            .expect("Should have a path");
+        assert_eq!(
+            r#"2>>34^>>>1323
+32v>>>35v5623
+32552456v>>54
+3446585845v52
+4546657867v>6
+14385987984v4
+44578769877v6
+36378779796v>
+465496798688v
+456467998645v
+12246868655<v
+25465488877v5
+43226746555v>
+"#,
+            map.render_path(&path)
+        );
+        assert_eq!(path.coords.len(), path.moves.len() + 1);
+        assert_eq!(Some(&(12, 12)), path.coords.last());
+        assert_eq!(
+            path.heat_loss,
+            path.coords
+                .iter()
+                .skip(1)
+                .map(|(x, y)| map[x * 13 + y])
+                .sum::<u32>()
+        );
+    }
+    #[test]
+    fn test_4() {
+        let map: Map = data_1().into();
+        let path = map
+            .solve(&Constraints::ultra_crucible())
+            .expect("Should have a path");
+        assert_eq!(vec![Dir::E; 7], path.moves[..7]);
+        let map: Map = "19\n91".into();
+        assert!(map.solve(&Constraints::ultra_crucible()).is_none());
+    }
+    #[test]
+    fn test_5() {
+        let map: Map = data().into();
+        [Constraints::crucible(), Constraints::ultra_crucible()]
+            .into_iter()
+            .for_each(|constraints| {
+                let a_star = map.solve(&constraints).expect("Should have a path");
+                let dijkstra = map
+                    .solve(&Constraints {
+                        search: Search::Dijkstra,
+                        ..constraints
+                    })
+                    .expect("Should have a path");
+                assert_eq!(dijkstra.heat_loss, a_star.heat_loss);
+                assert!(a_star.expanded <= dijkstra.expanded);
+            });
+    }
+    #[test]
+    fn test_6() {
+        let map: Map = data().into();
+        let back = map
+            .solve(&Constraints {
+                start: (12, 12),
+                goal: Some((0, 0)),
+                ..Constraints::crucible()
+            })
+            .expect("Should have a path");
+        assert_eq!(Some(&(0, 0)), back.coords.last());
+        assert_eq!(
+            back.heat_loss,
+            back.coords
+                .iter()
+                .skip(1)
+                .map(|(x, y)| map[x * 13 + y])
+                .sum::<u32>()
+        );
+
+        // With only right turns and no more than one move, the crucible spins in place.
+        let spinning = Constraints {
+            max_run: 1,
+            goal: Some((0, 0)),
+            turns: Turns {
+                left: false,
+                right: true,
+                u_turn: false,
+            },
+            ..Constraints::crucible()
+        };
+        let path = map.solve(&spinning).expect("Should have a path");
+        assert_eq!(vec![Dir::E, Dir::S, Dir::W, Dir::N], path.moves);
+        let u_turn = Constraints {
+            turns: Turns {
+                u_turn: true,
+                ..Turns::default()
+            },
+            ..spinning
+        };
+        let path = map.solve(&u_turn).expect("Should have a path");
+        assert_eq!(2, path.moves.len());
+    }
 }
@@ -1,5 +1,10 @@
+mod polygon;
+mod render;
+
 use std::{fmt::Debug, marker::PhantomData};
 
+use polygon::{Polygon, PolygonError};
+
 #[derive(Debug)]
 enum Direction {
     East,
@@ -27,9 +32,22 @@ enum ColoredParse {}
 struct Row<T> {
     pub(crate) dir: Direction,
     pub(crate) length: u32,
+    /// `#rrggbb` colour of the trench, kept even when it encodes the instruction.
+    pub(crate) color: u32,
     data: PhantomData<T>,
 }
 
+fn parse_color(value: &str) -> u32 {
+    let hex = value
+        .split(' ')
+        .nth(2)
+        .expect("Should have color")
+        .trim_matches('(')
+        .trim_matches(')')
+        .trim_start_matches('#');
+    u32::from_str_radix(hex, 16).expect("Should be an hex color")
+}
+
 impl From<&str> for Row<NormalParse> {
     fn from(value: &str) -> Self {
         let mut split = value.split(' ');
@@ -42,7 +60,8 @@ impl From<&str> for Row<NormalParse> {
         Self {
             dir,
             length,
-            data: PhantomData::default(),
+            color: parse_color(value),
+            data: PhantomData,
         }
     }
 }
@@ -75,6 +94,7 @@ impl From<&str> for Row<ColoredParse> {
         Self {
             dir,
             length: tot,
+            color: parse_color(value),
             data: Default::default(),
         }
     }
@@ -82,8 +102,10 @@ impl From<&str> for Row<ColoredParse> {
 
 #[derive(Debug)]
 struct Map {
-    pub(crate) map: Vec<(i64, i64)>,
-    pub(crate) area: u32,
+    /// Corners of the trench, starting and hopefully ending on `(0, 0)`.
+    pub(crate) map: Vec<(i128, i128)>,
+    /// Colour of the segment starting on the corner with the same index.
+    pub(crate) colors: Vec<u32>,
 }
 
 impl<T> From<Vec<Row<T>>> for Map {
@@ -91,67 +113,105 @@ impl<T> From<Vec<Row<T>>> for Map {
         let mut x = 0;
         let mut y = 0;
         let mut map = vec![(0, 0)];
-        let mut area = 2;
         for r in rows.iter() {
-            let dir: (i64, i64) = match r.dir {
+            let dir: (i128, i128) = match r.dir {
                 Direction::East => (0, 1),
                 Direction::North => (-1, 0),
                 Direction::West => (0, -1),
                 Direction::South => (1, 0),
             };
-            x += dir.0 * r.length as i64;
-            y += dir.1 * r.length as i64;
-            area += r.length;
+            x += dir.0 * r.length as i128;
+            y += dir.1 * r.length as i128;
             map.push((x, y))
         }
-        Self {
-            map,
-            area: area / 2,
-        }
+        let colors = rows.iter().map(|r| r.color).collect();
+        Self { map, colors }
     }
 }
 
 impl Map {
-    fn eval_shoelace(&self) -> i64 {
-        self.map
-            .windows(2)
-            .fold(0, |acc, coords| {
-                acc + coords[0].0 * coords[1].1 - coords[0].1 * coords[1].0
-            })
-            .abs()
-            / 2
+    /// Cubic meters of lava held by the lagoon : the trench and its interior.
+    fn eval_lagoon(self) -> Result<i128, PolygonError> {
+        let polygon = Polygon::try_from(self.map)?;
+        Ok(polygon.lattice_points())
     }
 }
 
-pub(crate) fn eval_file(file: &str) -> i64 {
-    let rows = file
-        .lines()
-        .filter(|l| !l.is_empty())
-        .map(|l| l.into())
-        .collect::<Vec<Row<NormalParse>>>();
-    let map: Map = rows.into();
-    map.eval_shoelace() + map.area as i64
+fn describe(map: Map) -> String {
+    match Polygon::try_from(map.map) {
+        Ok(polygon) => format!(
+            "area : {} // perimeter : {} // interior : {} // lagoon : {}",
+            polygon.area(),
+            polygon.perimeter(),
+            polygon.interior_points(),
+            polygon.lattice_points()
+        ),
+        Err(e) => format!("invalid dig plan : {}", e),
+    }
 }
 
-pub(crate) fn eval_file_2(file: &str) -> i64 {
-    let rows = file
-        .lines()
+fn parse_map<T>(file: &str) -> Map
+where
+    for<'a> Row<T>: From<&'a str>,
+{
+    file.lines()
         .filter(|l| !l.is_empty())
         .map(|l| l.into())
-        .collect::<Vec<Row<ColoredParse>>>();
-    let map: Map = rows.into();
-    map.eval_shoelace() + map.area as i64
+        .collect::<Vec<Row<T>>>()
+        .into()
+}
+
+pub(crate) fn eval_file(file: &str) -> Result<i128, PolygonError> {
+    parse_map::<NormalParse>(file).eval_lagoon()
+}
+
+pub(crate) fn eval_file_2(file: &str) -> Result<i128, PolygonError> {
+    parse_map::<ColoredParse>(file).eval_lagoon()
+}
+
+pub(crate) fn print_polygons(file: &str) {
+    println!("plan : {}", describe(parse_map::<NormalParse>(file)));
+    print!("colors : {}", describe(parse_map::<ColoredParse>(file)));
+}
+
+/// SVG export of the plan, read as in part 1 or, with `colored`, as in part 2.
+pub(crate) fn print_svg(file: &str, colored: bool) {
+    let map = if colored {
+        parse_map::<ColoredParse>(file)
+    } else {
+        parse_map::<NormalParse>(file)
+    };
+    print!("{}", render::to_svg(&map.map, &map.colors));
+}
+
+pub(crate) fn print_ascii(file: &str) {
+    let map = parse_map::<NormalParse>(file);
+    match render::to_ascii(&map.map) {
+        Some(drawing) => println!("{}", drawing),
+        None => println!(
+            "plan wider than {} meters, export it as svg instead",
+            render::MAX_ASCII_SPAN
+        ),
+    }
+    print!("{}", describe(map));
+}
+
+fn print_res(res: Result<i128, PolygonError>) {
+    match res {
+        Ok(res) => print!("res : {}", res),
+        Err(e) => print!("invalid dig plan : {}", e),
+    }
 }
 pub(crate) fn print_sol_1(file: &str) {
-    print!("res : {}", eval_file(file));
+    print_res(eval_file(file));
 }
 pub(crate) fn print_sol_2(file: &str) {
-    print!("res : {}", eval_file_2(file));
+    print_res(eval_file_2(file));
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{eval_file, eval_file_2};
+    use super::{eval_file, eval_file_2, parse_map, polygon::PolygonError, render, NormalParse};
 
     fn data() -> &'static str {
         r#"
@@ -172,10 +232,34 @@ U 2 (#7a21e3)"#
     }
     #[test]
     fn test_0() {
-        assert_eq!(62, eval_file(data()));
+        assert_eq!(Ok(62), eval_file(data()));
     }
     #[test]
     fn test_1() {
-        assert_eq!(952408144115, eval_file_2(data()));
+        assert_eq!(Ok(952408144115), eval_file_2(data()));
+    }
+    #[test]
+    fn test_2() {
+        assert_eq!(
+            Err(PolygonError::NotClosed {
+                start: (0, 0),
+                end: (1, 6)
+            }),
+            eval_file("R 6 (#70c710)\nD 1 (#0dc571)")
+        );
+        assert_eq!(
+            Err(PolygonError::Backtrack { segment: 1 }),
+            eval_file("R 6 (#70c710)\nL 6 (#0dc571)")
+        );
+    }
+    #[test]
+    fn test_3() {
+        let map = parse_map::<NormalParse>(data());
+        assert_eq!(0x70c710, map.colors[0]);
+        let drawing = render::to_ascii(&map.map).expect("Should be small enough");
+        assert_eq!(38, drawing.matches('#').count());
+        assert_eq!(62, drawing.matches(['#', '~']).count());
+        let svg = render::to_svg(&map.map, &map.colors);
+        assert!(svg.contains("stroke=\"#7a21e3\""));
     }
 }
@@ -0,0 +1,200 @@
+use std::fmt::Display;
+
+use num::integer::gcd;
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum PolygonError {
+    /// The last vertex is not back on the first one.
+    NotClosed {
+        start: (i128, i128),
+        end: (i128, i128),
+    },
+    /// A segment of length 0.
+    EmptySegment { segment: usize },
+    /// A segment going back over the previous one.
+    Backtrack { segment: usize },
+    /// Two non consecutive segments touching each other.
+    SelfIntersection { first: usize, second: usize },
+}
+
+impl Display for PolygonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotClosed { start, end } => {
+                write!(f, "polygon starts at {start:?} but ends at {end:?}")
+            }
+            Self::EmptySegment { segment } => write!(f, "segment {segment} has a length of 0"),
+            Self::Backtrack { segment } => {
+                write!(f, "segment {segment} goes back over the previous one")
+            }
+            Self::SelfIntersection { first, second } => {
+                write!(f, "segments {first} and {second} intersect")
+            }
+        }
+    }
+}
+
+/// Closed simple polygon with integer vertices, the first vertex being repeated at the end.
+#[derive(Debug)]
+pub(crate) struct Polygon {
+    vertices: Vec<(i128, i128)>,
+}
+
+/// Sign of the turn `a -> b -> c` : positive counterclockwise, 0 when aligned.
+fn orientation(a: (i128, i128), b: (i128, i128), c: (i128, i128)) -> i128 {
+    ((b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)).signum()
+}
+
+/// `p` lying in the bounding box of `a` and `b`, meaningful when the three are aligned.
+fn in_box(a: (i128, i128), b: (i128, i128), p: (i128, i128)) -> bool {
+    a.0.min(b.0) <= p.0 && p.0 <= a.0.max(b.0) && a.1.min(b.1) <= p.1 && p.1 <= a.1.max(b.1)
+}
+
+fn segments_touch(a: (i128, i128), b: (i128, i128), c: (i128, i128), d: (i128, i128)) -> bool {
+    let (o1, o2) = (orientation(a, b, c), orientation(a, b, d));
+    let (o3, o4) = (orientation(c, d, a), orientation(c, d, b));
+    (o1 != o2 && o3 != o4)
+        || (o1 == 0 && in_box(a, b, c))
+        || (o2 == 0 && in_box(a, b, d))
+        || (o3 == 0 && in_box(c, d, a))
+        || (o4 == 0 && in_box(c, d, b))
+}
+
+impl TryFrom<Vec<(i128, i128)>> for Polygon {
+    type Error = PolygonError;
+    fn try_from(vertices: Vec<(i128, i128)>) -> Result<Self, Self::Error> {
+        let start = *vertices.first().unwrap_or(&(0, 0));
+        let end = *vertices.last().unwrap_or(&(0, 0));
+        if start != end {
+            return Err(PolygonError::NotClosed { start, end });
+        }
+        let segments = vertices
+            .windows(2)
+            .map(|w| (w[0], w[1]))
+            .collect::<Vec<_>>();
+        let nbr_segments = segments.len();
+        for (i, (a, b)) in segments.iter().enumerate() {
+            if a == b {
+                return Err(PolygonError::EmptySegment { segment: i });
+            }
+        }
+        for i in 0..nbr_segments {
+            // Aligned consecutive segments pointing in opposite directions overlap.
+            let (a, b) = segments[i];
+            let (_, c) = segments[(i + 1) % nbr_segments];
+            let dot = (b.0 - a.0) * (c.0 - b.0) + (b.1 - a.1) * (c.1 - b.1);
+            if orientation(a, b, c) == 0 && dot < 0 {
+                return Err(PolygonError::Backtrack {
+                    segment: (i + 1) % nbr_segments,
+                });
+            }
+        }
+        for i in 0..nbr_segments {
+            // Consecutive segments share a vertex, including the last and the first one.
+            for j in (i + 2)..nbr_segments {
+                if i == 0 && j == nbr_segments - 1 {
+                    continue;
+                }
+                let ((a, b), (c, d)) = (segments[i], segments[j]);
+                if segments_touch(a, b, c, d) {
+                    return Err(PolygonError::SelfIntersection {
+                        first: i,
+                        second: j,
+                    });
+                }
+            }
+        }
+        Ok(Self { vertices })
+    }
+}
+
+impl Polygon {
+    /// Shoelace formula, twice the area to stay an integer.
+    pub(crate) fn double_area(&self) -> i128 {
+        self.vertices
+            .windows(2)
+            .map(|w| w[0].0 * w[1].1 - w[0].1 * w[1].0)
+            .sum::<i128>()
+            .abs()
+    }
+
+    pub(crate) fn area(&self) -> i128 {
+        self.double_area() / 2
+    }
+
+    /// Number of lattice points on the boundary, also the perimeter of an
+    /// axis-aligned polygon.
+    pub(crate) fn boundary_points(&self) -> i128 {
+        self.vertices
+            .windows(2)
+            .map(|w| gcd((w[1].0 - w[0].0).abs(), (w[1].1 - w[0].1).abs()))
+            .sum()
+    }
+
+    pub(crate) fn perimeter(&self) -> i128 {
+        self.boundary_points()
+    }
+
+    /// Pick's theorem : `A = I + B / 2 - 1`.
+    pub(crate) fn interior_points(&self) -> i128 {
+        (self.double_area() - self.boundary_points()) / 2 + 1
+    }
+
+    /// Every lattice point inside or on the polygon.
+    pub(crate) fn lattice_points(&self) -> i128 {
+        self.interior_points() + self.boundary_points()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Polygon, PolygonError};
+
+    fn square() -> Vec<(i128, i128)> {
+        vec![(0, 0), (0, 4), (4, 4), (4, 0), (0, 0)]
+    }
+
+    #[test]
+    fn test_0() {
+        let polygon = Polygon::try_from(square()).expect("Is valid");
+        assert_eq!(16, polygon.area());
+        assert_eq!(16, polygon.perimeter());
+        assert_eq!(9, polygon.interior_points());
+        assert_eq!(25, polygon.lattice_points());
+    }
+    #[test]
+    fn test_1() {
+        let big = 1_i128 << 60;
+        let polygon = Polygon::try_from(vec![(0, 0), (0, big), (big, big), (big, 0), (0, 0)])
+            .expect("Is valid");
+        assert_eq!(big * big, polygon.area());
+    }
+    #[test]
+    fn test_2() {
+        let mut open = square();
+        open.pop();
+        assert_eq!(
+            Some(PolygonError::NotClosed {
+                start: (0, 0),
+                end: (4, 0)
+            }),
+            Polygon::try_from(open).err()
+        );
+        assert_eq!(
+            Some(PolygonError::Backtrack { segment: 2 }),
+            Polygon::try_from(vec![(0, 0), (0, 4), (0, 6), (0, 2), (4, 2), (0, 0)]).err()
+        );
+        assert_eq!(
+            Some(PolygonError::EmptySegment { segment: 1 }),
+            Polygon::try_from(vec![(0, 0), (0, 4), (0, 4), (4, 4), (0, 0)]).err()
+        );
+        // Figure eight : the second and fourth segments cross.
+        assert_eq!(
+            Some(PolygonError::SelfIntersection {
+                first: 1,
+                second: 3
+            }),
+            Polygon::try_from(vec![(0, 0), (0, 2), (2, 0), (2, 2), (0, 0)]).err()
+        );
+    }
+}
@@ -0,0 +1,170 @@
+/// Widest span (in meters) drawn at its true scale, larger plans get compressed.
+const MAX_SVG_SPAN: i128 = 2000;
+/// Distance between two consecutive distinct coordinates once compressed.
+const COMPRESSED_STEP: i128 = 10;
+/// Biggest plan (in meters, on both axes) rendered in the terminal.
+pub(crate) const MAX_ASCII_SPAN: i128 = 200;
+
+const TRENCH: char = '#';
+const LAGOON: char = '~';
+const GROUND: char = '.';
+
+/// Position of every distinct coordinate of an axis once drawn.
+/// Small axes keep their real proportions, big ones only keep the ordering
+/// of the coordinates, each of them being `COMPRESSED_STEP` apart.
+struct Axis {
+    values: Vec<i128>,
+    compressed: bool,
+}
+
+impl Axis {
+    fn new(mut values: Vec<i128>) -> Self {
+        values.sort_unstable();
+        values.dedup();
+        let compressed = values[values.len() - 1] - values[0] > MAX_SVG_SPAN;
+        Self { values, compressed }
+    }
+
+    fn position(&self, value: i128) -> i128 {
+        if self.compressed {
+            let rank = self
+                .values
+                .binary_search(&value)
+                .expect("Should be a known coordinate");
+            rank as i128 * COMPRESSED_STEP
+        } else {
+            value - self.values[0]
+        }
+    }
+
+    fn len(&self) -> i128 {
+        self.position(self.values[self.values.len() - 1])
+    }
+}
+
+/// SVG drawing of the trench, the segment `i` going from `corners[i]` to
+/// `corners[i + 1]` being stroked with `colors[i]`.
+pub(crate) fn to_svg(corners: &[(i128, i128)], colors: &[u32]) -> String {
+    let rows = Axis::new(corners.iter().map(|c| c.0).collect());
+    let cols = Axis::new(corners.iter().map(|c| c.1).collect());
+    let points = corners
+        .iter()
+        .map(|&(x, y)| (cols.position(y), rows.position(x)))
+        .collect::<Vec<_>>();
+
+    let mut ret = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"-1 -1 {} {}\">\n",
+        cols.len() + 2,
+        rows.len() + 2
+    );
+    ret.push_str(&format!(
+        "  <polygon points=\"{}\" fill=\"#dddddd\" />\n",
+        points
+            .iter()
+            .map(|(x, y)| format!("{x},{y}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    ));
+    points.windows(2).zip(colors).for_each(|(w, color)| {
+        ret.push_str(&format!(
+            "  <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"#{:06x}\" stroke-linecap=\"square\" />\n",
+            w[0].0, w[0].1, w[1].0, w[1].1, color
+        ));
+    });
+    ret.push_str("</svg>\n");
+    ret
+}
+
+/// Terminal drawing of the trench (`#`) and of the lava it holds (`~`),
+/// `None` when the plan does not fit in `MAX_ASCII_SPAN` meters.
+pub(crate) fn to_ascii(corners: &[(i128, i128)]) -> Option<String> {
+    let min_x = corners.iter().map(|c| c.0).min()?;
+    let max_x = corners.iter().map(|c| c.0).max()?;
+    let min_y = corners.iter().map(|c| c.1).min()?;
+    let max_y = corners.iter().map(|c| c.1).max()?;
+    if max_x - min_x >= MAX_ASCII_SPAN || max_y - min_y >= MAX_ASCII_SPAN {
+        return None;
+    }
+    let height = (max_x - min_x + 1) as usize;
+    let width = (max_y - min_y + 1) as usize;
+    let mut grid = vec![vec![GROUND; width]; height];
+
+    corners.windows(2).for_each(|w| {
+        let (dx, dy) = ((w[1].0 - w[0].0).signum(), (w[1].1 - w[0].1).signum());
+        let (mut x, mut y) = w[0];
+        loop {
+            grid[(x - min_x) as usize][(y - min_y) as usize] = TRENCH;
+            if (x, y) == w[1] {
+                break;
+            }
+            x += dx;
+            y += dy;
+        }
+    });
+
+    // Vertical segments as `(col, top, bottom)`, a row crossing one when in `[top, bottom)`.
+    let verticals = corners
+        .windows(2)
+        .filter(|w| w[0].1 == w[1].1 && w[0].0 != w[1].0)
+        .map(|w| (w[0].1, w[0].0.min(w[1].0), w[0].0.max(w[1].0)))
+        .collect::<Vec<_>>();
+    grid.iter_mut().enumerate().for_each(|(i, line)| {
+        let x = i as i128 + min_x;
+        line.iter_mut().enumerate().for_each(|(j, c)| {
+            let y = j as i128 + min_y;
+            let crossings = verticals
+                .iter()
+                .filter(|(col, top, bottom)| *col > y && *top <= x && x < *bottom)
+                .count();
+            if *c == GROUND && crossings % 2 == 1 {
+                *c = LAGOON;
+            }
+        })
+    });
+
+    Some(
+        grid.into_iter()
+            .map(|line| line.into_iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{to_ascii, to_svg};
+
+    fn square() -> Vec<(i128, i128)> {
+        vec![(0, 0), (0, 3), (3, 3), (3, 0), (0, 0)]
+    }
+    #[test]
+    fn test_0() {
+        assert_eq!(
+            Some("####\n#~~#\n#~~#\n####".to_string()),
+            to_ascii(&square())
+        );
+        assert_eq!(
+            None,
+            to_ascii(&[(0, 0), (0, 1000), (1, 1000), (1, 0), (0, 0)])
+        );
+    }
+    #[test]
+    fn test_1() {
+        let svg = to_svg(&square(), &[0xff0000, 0x00ff00, 0x0000ff, 0x123456]);
+        assert!(svg.contains("viewBox=\"-1 -1 5 5\""));
+        assert!(svg.contains("stroke=\"#00ff00\""));
+        assert_eq!(4, svg.matches("<line").count());
+        // Far away corners only keep their ordering.
+        let huge = to_svg(
+            &[
+                (0, 0),
+                (0, 1 << 40),
+                (1 << 40, 1 << 40),
+                (1 << 40, 0),
+                (0, 0),
+            ],
+            &[0; 4],
+        );
+        assert!(huge.contains("viewBox=\"-1 -1 12 12\""));
+    }
+}
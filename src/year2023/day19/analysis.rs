@@ -0,0 +1,204 @@
+use std::fmt::Display;
+
+use super::program::{Program, Target};
+
+/// What the workflows do with every possible rating, starting from `in`.
+#[derive(Debug)]
+pub(crate) struct Analysis<'a> {
+    program: &'a Program,
+    /// `fired[w][r]` when some rating leaves workflow `w` through rule `r`,
+    /// the default destination being the last one.
+    pub(crate) fired: Vec<Vec<bool>>,
+    /// Workflows some rating goes through.
+    pub(crate) reached: Vec<bool>,
+    /// Verdict of the reached workflows always accepting or always rejecting.
+    pub(crate) constant: Vec<Option<bool>>,
+}
+
+impl<'a> From<&'a Program> for Analysis<'a> {
+    fn from(program: &'a Program) -> Self {
+        let mut fired = program
+            .workflows
+            .iter()
+            .map(|w| vec![false; w.rules.len() + 1])
+            .collect::<Vec<_>>();
+        program.propagate(|workflow, rule, _| {
+            let rule = rule.unwrap_or(fired[workflow].len() - 1);
+            fired[workflow][rule] = true;
+        });
+        let reached = fired.iter().map(|f| f.iter().any(|f| *f)).collect();
+        let mut ret = Self {
+            program,
+            fired,
+            reached,
+            constant: vec![None; program.workflows.len()],
+        };
+        let mut memo = vec![None; program.workflows.len()];
+        ret.constant = (0..program.workflows.len())
+            .map(|w| ret.verdict(Target::Workflow(w), &mut memo))
+            .collect();
+        ret
+    }
+}
+
+impl Analysis<'_> {
+    /// Targets of the rules of `workflow` that fire, the default one included.
+    fn live_targets(&self, workflow: usize) -> impl Iterator<Item = Target> + '_ {
+        let compiled = &self.program.workflows[workflow];
+        compiled
+            .rules
+            .iter()
+            .map(|(_, t)| *t)
+            .chain(std::iter::once(compiled.default))
+            .zip(self.fired[workflow].iter())
+            .filter(|(_, fired)| **fired)
+            .map(|(t, _)| t)
+    }
+
+    /// The verdict of a workflow only depends on the verdicts of the ones it
+    /// sends ratings to. Rules firing from different paths can still loop, a
+    /// workflow being looked at again while in progress then counting as mixed.
+    fn verdict(&self, target: Target, memo: &mut Vec<Option<Option<bool>>>) -> Option<bool> {
+        match target {
+            Target::Accept => Some(true),
+            Target::Reject => Some(false),
+            Target::Workflow(w) => {
+                if let Some(verdict) = memo[w] {
+                    return verdict;
+                }
+                memo[w] = Some(None);
+                let verdict = if self.reached[w] {
+                    let verdicts = self
+                        .live_targets(w)
+                        .map(|t| self.verdict(t, memo))
+                        .collect::<Vec<_>>();
+                    if verdicts.iter().all(|v| *v == verdicts[0]) {
+                        verdicts[0]
+                    } else {
+                        None
+                    }
+                } else {
+                    None
+                };
+                memo[w] = Some(verdict);
+                verdict
+            }
+        }
+    }
+
+    /// `(workflow, rule)` of the rules no rating ever matches, default ones excluded.
+    pub(crate) fn dead_rules(&self) -> Vec<(usize, usize)> {
+        self.fired
+            .iter()
+            .enumerate()
+            .filter(|(w, _)| self.reached[*w])
+            .flat_map(|(w, fired)| {
+                fired[..fired.len() - 1]
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, f)| !**f)
+                    .map(move |(r, _)| (w, r))
+            })
+            .collect()
+    }
+
+    fn resolve(&self, target: Target) -> Target {
+        match target {
+            Target::Workflow(w) => match self.constant[w] {
+                Some(true) => Target::Accept,
+                Some(false) => Target::Reject,
+                None => target,
+            },
+            t => t,
+        }
+    }
+
+    fn target_text(&self, target: Target) -> String {
+        match target {
+            Target::Accept => String::from("A"),
+            Target::Reject => String::from("R"),
+            Target::Workflow(w) => self.program.names[w].clone(),
+        }
+    }
+
+    /// Equivalent workflows, without dead rules nor unreachable workflows,
+    /// and with constant workflows replaced by their verdict.
+    pub(crate) fn minimize(&self) -> String {
+        let program = self.program;
+        let mut lines = vec![];
+        for (w, compiled) in program.workflows.iter().enumerate() {
+            if !self.reached[w] || (self.constant[w].is_some() && w != program.start) {
+                continue;
+            }
+            let mut rules = compiled
+                .rules
+                .iter()
+                .zip(self.fired[w].iter())
+                .filter(|(_, fired)| **fired)
+                .map(|((conditions, t), _)| (conditions, self.resolve(*t)))
+                .collect::<Vec<_>>();
+            let mut default = if self.fired[w][compiled.rules.len()] {
+                self.resolve(compiled.default)
+            } else {
+                // Whatever reaches the last firing rule matches it.
+                rules.pop().expect("Should fire somewhere").1
+            };
+            if let Some(verdict) = self.constant[w] {
+                rules.clear();
+                default = if verdict {
+                    Target::Accept
+                } else {
+                    Target::Reject
+                };
+            }
+            while rules.last().is_some_and(|(_, t)| *t == default) {
+                rules.pop();
+            }
+            let mut parts = rules
+                .iter()
+                .map(|(conditions, t)| {
+                    format!(
+                        "{}:{}",
+                        program.condition_text(conditions),
+                        self.target_text(*t)
+                    )
+                })
+                .collect::<Vec<_>>();
+            parts.push(self.target_text(default));
+            lines.push(format!("{}{{{}}}", program.names[w], parts.join(",")));
+        }
+        lines.join("\n")
+    }
+}
+
+impl Display for Analysis<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let program = self.program;
+        writeln!(f, "dead rules :")?;
+        for (w, r) in self.dead_rules() {
+            let (conditions, target) = &program.workflows[w].rules[r];
+            writeln!(
+                f,
+                "  {} rule {} ({}:{})",
+                program.names[w],
+                r,
+                program.condition_text(conditions),
+                self.target_text(*target)
+            )?;
+        }
+        let unreachable = (0..program.workflows.len())
+            .filter(|w| !self.reached[*w])
+            .map(|w| program.names[w].as_str())
+            .collect::<Vec<_>>();
+        writeln!(f, "unreachable workflows : {}", unreachable.join(", "))?;
+        let constant = self
+            .constant
+            .iter()
+            .enumerate()
+            .filter_map(|(w, verdict)| {
+                verdict.map(|v| format!("{} -> {}", program.names[w], if v { "A" } else { "R" }))
+            })
+            .collect::<Vec<_>>();
+        write!(f, "constant workflows : {}", constant.join(", "))
+    }
+}
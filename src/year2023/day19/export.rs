@@ -0,0 +1,119 @@
+use super::{
+    program::{Program, Target},
+    Bounds,
+};
+
+fn json_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// `{"categories": [...], "regions": [{"x": [lo, hi], ..., "combinations": n}], "combinations": total}`
+pub(crate) fn regions_json(categories: &[String], regions: &[Bounds]) -> String {
+    let names = categories
+        .iter()
+        .map(|c| json_string(c))
+        .collect::<Vec<_>>();
+    let regions_text = regions
+        .iter()
+        .map(|b| {
+            let ranges = categories
+                .iter()
+                .zip(b.iter())
+                .map(|(c, (lo, hi))| format!("{}: [{}, {}]", json_string(c), lo, hi))
+                .collect::<Vec<_>>();
+            format!(
+                "    {{{}, \"combinations\": {}}}",
+                ranges.join(", "),
+                b.eval_opportunities()
+            )
+        })
+        .collect::<Vec<_>>();
+    let total: u128 = regions.iter().map(|b| b.eval_opportunities()).sum();
+    format!(
+        "{{\n  \"categories\": [{}],\n  \"regions\": [\n{}\n  ],\n  \"combinations\": {}\n}}\n",
+        names.join(", "),
+        regions_text.join(",\n"),
+        total
+    )
+}
+
+/// One line per region, with the inclusive bounds of every category.
+pub(crate) fn regions_csv(categories: &[String], regions: &[Bounds]) -> String {
+    let mut header = categories
+        .iter()
+        .flat_map(|c| [format!("{c}_min"), format!("{c}_max")])
+        .collect::<Vec<_>>();
+    header.push(String::from("combinations"));
+    let mut lines = vec![header.join(",")];
+    regions.iter().for_each(|b| {
+        let mut values = b
+            .iter()
+            .flat_map(|(lo, hi)| [lo.to_string(), hi.to_string()])
+            .collect::<Vec<_>>();
+        values.push(b.eval_opportunities().to_string());
+        lines.push(values.join(","));
+    });
+    lines.join("\n") + "\n"
+}
+
+/// Graphviz rendering of the workflows, each edge being labelled with the
+/// condition of its rule, default destinations being dashed.
+pub(crate) fn workflows_dot(program: &Program) -> String {
+    let node = |target: Target| match target {
+        Target::Accept => String::from("\"A\""),
+        Target::Reject => String::from("\"R\""),
+        Target::Workflow(w) => json_string(&program.names[w]),
+    };
+    let mut lines = vec![
+        String::from("digraph workflows {"),
+        String::from("  \"A\" [shape=doublecircle, color=green];"),
+        String::from("  \"R\" [shape=doublecircle, color=red];"),
+        format!("  {} [shape=box];", node(Target::Workflow(program.start))),
+    ];
+    program
+        .workflows
+        .iter()
+        .enumerate()
+        .for_each(|(w, workflow)| {
+            let from = node(Target::Workflow(w));
+            workflow.rules.iter().for_each(|(conditions, target)| {
+                lines.push(format!(
+                    "  {} -> {} [label={}];",
+                    from,
+                    node(*target),
+                    json_string(&program.condition_text(conditions))
+                ))
+            });
+            lines.push(format!(
+                "  {} -> {} [style=dashed];",
+                from,
+                node(workflow.default)
+            ));
+        });
+    lines.push(String::from("}"));
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{regions_csv, regions_json, Bounds};
+
+    fn categories() -> Vec<String> {
+        vec![String::from("x"), String::from("m")]
+    }
+    #[test]
+    fn test_0() {
+        let regions = [
+            Bounds(vec![(1, 10), (5, 5)]),
+            Bounds(vec![(11, 20), (1, 2)]),
+        ];
+        assert_eq!(
+            "{\n  \"categories\": [\"x\", \"m\"],\n  \"regions\": [\n    {\"x\": [1, 10], \"m\": [5, 5], \"combinations\": 10},\n    {\"x\": [11, 20], \"m\": [1, 2], \"combinations\": 20}\n  ],\n  \"combinations\": 30\n}\n",
+            regions_json(&categories(), &regions)
+        );
+        assert_eq!(
+            "x_min,x_max,m_min,m_max,combinations\n1,10,5,5,10\n11,20,1,2,20\n",
+            regions_csv(&categories(), &regions)
+        );
+    }
+}
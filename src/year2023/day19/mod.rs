@@ -1,15 +1,44 @@
-#[derive(Debug, Clone)]
-enum Category {
-    X,
-    M,
-    A,
-    S,
-}
+mod analysis;
+mod export;
+mod program;
 
-#[derive(Debug, Clone)]
+use std::{collections::BTreeMap, fmt::Display, ops::Deref};
+
+use analysis::Analysis;
+use program::{CompileError, Program};
+
+/// Smallest and biggest values a rating can take.
+const MIN_RATING: u64 = 1;
+const MAX_RATING: u64 = 4000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Operation {
     Greater,
     Lesser,
+    GreaterOrEqual,
+    LesserOrEqual,
+    Equal,
+    NotEqual,
+}
+
+impl Operation {
+    /// Operators sorted so that `<=` is tried before `<`.
+    const SYMBOLS: [(&'static str, Operation); 6] = [
+        ("<=", Self::LesserOrEqual),
+        (">=", Self::GreaterOrEqual),
+        ("==", Self::Equal),
+        ("!=", Self::NotEqual),
+        ("<", Self::Lesser),
+        (">", Self::Greater),
+    ];
+
+    fn symbol(&self) -> &'static str {
+        Self::SYMBOLS
+            .iter()
+            .find(|(_, op)| op == self)
+            .map(|(symbol, _)| *symbol)
+            .expect("Should have a symbol")
+    }
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
@@ -24,21 +53,6 @@ enum RuleDest {
     OtherRule(String),
 }
 
-impl RuleDest {
-    fn is_end(&self) -> bool {
-        match self {
-            Self::End(_) => true,
-            Self::OtherRule(_) => false,
-        }
-    }
-    fn is_approved(&self) -> bool {
-        match self {
-            Self::End(EndDest::A) => true,
-            _ => false,
-        }
-    }
-}
-
 impl From<&str> for RuleDest {
     fn from(rule: &str) -> Self {
         match rule {
@@ -55,69 +69,106 @@ impl Default for RuleDest {
     }
 }
 
-#[derive(Debug, Clone)]
-struct Rule {
-    pub(crate) category: Category,
+/// `category operation nbr`, the category being a name once parsed and an
+/// index in the rating once compiled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Comparison<C> {
+    pub(crate) category: C,
     pub(crate) operation: Operation,
     pub(crate) nbr: u64,
-    pub(crate) dst: RuleDest,
 }
 
-impl Rule {
+impl From<&str> for Comparison<String> {
+    fn from(value: &str) -> Self {
+        let start = value
+            .find(['<', '>', '=', '!'])
+            .expect("Should have an operator");
+        let (operation, nbr) = Operation::SYMBOLS
+            .iter()
+            .find_map(|(symbol, op)| value[start..].strip_prefix(symbol).map(|nbr| (*op, nbr)))
+            .expect("Should be a known operator");
+        Self {
+            category: value[..start].to_string(),
+            operation,
+            nbr: nbr.parse().expect("Should be nbr"),
+        }
+    }
+}
+
+impl Comparison<usize> {
     fn is_valid(&self, rating: &Rating) -> bool {
-        match self.category {
-            Category::X => match self.operation {
-                Operation::Greater => rating.x > self.nbr,
-                Operation::Lesser => rating.x < self.nbr,
-            },
-            Category::M => match self.operation {
-                Operation::Greater => rating.m > self.nbr,
-                Operation::Lesser => rating.m < self.nbr,
-            },
-            Category::A => match self.operation {
-                Operation::Greater => rating.a > self.nbr,
-                Operation::Lesser => rating.a < self.nbr,
-            },
-            Category::S => match self.operation {
-                Operation::Greater => rating.s > self.nbr,
-                Operation::Lesser => rating.s < self.nbr,
-            },
+        let value = rating[self.category];
+        match self.operation {
+            Operation::Greater => value > self.nbr,
+            Operation::Lesser => value < self.nbr,
+            Operation::GreaterOrEqual => value >= self.nbr,
+            Operation::LesserOrEqual => value <= self.nbr,
+            Operation::Equal => value == self.nbr,
+            Operation::NotEqual => value != self.nbr,
         }
     }
+
+    /// Disjoint inclusive ranges of ratings satisfying the comparison.
+    fn intervals(&self) -> Vec<(u64, u64)> {
+        let below = (MIN_RATING, self.nbr.saturating_sub(1));
+        let above = (self.nbr.saturating_add(1), MAX_RATING);
+        let ranges = match self.operation {
+            Operation::Greater => vec![above],
+            Operation::Lesser => vec![below],
+            Operation::GreaterOrEqual => vec![(self.nbr, MAX_RATING)],
+            Operation::LesserOrEqual => vec![(MIN_RATING, self.nbr)],
+            Operation::Equal => vec![(self.nbr, self.nbr)],
+            Operation::NotEqual => vec![below, above],
+        };
+        ranges
+            .into_iter()
+            .map(|(lo, hi)| (lo.max(MIN_RATING), hi.min(MAX_RATING)))
+            .filter(|(lo, hi)| lo <= hi)
+            .collect()
+    }
+}
+
+/// Conjunction of comparisons, matching when all of them hold.
+fn matches(conditions: &[Comparison<usize>], rating: &Rating) -> bool {
+    conditions.iter().all(|c| c.is_valid(rating))
+}
+
+/// Disjoint boxes of `dims` dimensions in which every comparison holds.
+fn matching_bounds(conditions: &[Comparison<usize>], dims: usize) -> Vec<Bounds> {
+    conditions
+        .iter()
+        .fold(vec![Bounds::new(dims)], |boxes, comparison| {
+            let intervals = comparison.intervals();
+            boxes
+                .iter()
+                .flat_map(|b| {
+                    intervals
+                        .iter()
+                        .filter_map(|range| b.restrict(comparison.category, *range))
+                })
+                .collect()
+        })
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    pub(crate) conditions: Vec<Comparison<String>>,
+    pub(crate) dst: RuleDest,
 }
 
 impl From<&str> for Rule {
     fn from(value: &str) -> Self {
-        let mut split = value.split(':');
-        let mut start = split.next().expect("Should be present").chars();
-        let category = match start.next() {
-            Some('x') => Category::X,
-            Some('m') => Category::M,
-            Some('a') => Category::A,
-            Some('s') => Category::S,
-            _ => unreachable!(),
-        };
-        let operation = match start.next() {
-            Some('<') => Operation::Lesser,
-            Some('>') => Operation::Greater,
-            _ => unreachable!(),
-        };
-        let nbr = start.fold(0, |acc, v| {
-            acc * 10 + v.to_digit(10).expect("should be nbr") as u64
-        });
-        let dst = split.next().expect("should have dst").into();
+        let (conditions, dst) = value.split_once(':').expect("should have dst");
         Self {
-            category,
-            operation,
-            nbr,
-            dst,
+            conditions: conditions.split('&').map(|c| c.into()).collect(),
+            dst: dst.into(),
         }
     }
 }
 
 #[derive(Debug)]
 struct Workflow {
-    pub(crate) name: RuleDest,
+    pub(crate) name: String,
     pub(crate) rules: Vec<Rule>,
     pub(crate) default_dest: RuleDest,
 }
@@ -125,7 +176,7 @@ struct Workflow {
 impl From<&str> for Workflow {
     fn from(value: &str) -> Self {
         let mut split = value.trim_end_matches('}').split('{');
-        let name = RuleDest::OtherRule(split.next().expect("Should have a name").to_string());
+        let name = split.next().expect("Should have a name").to_string();
         let mut rules_and_dest = split
             .next()
             .expect("Should have rules")
@@ -148,160 +199,104 @@ impl From<&str> for Workflow {
     }
 }
 
-struct Rating {
-    pub(crate) x: u64,
-    pub(crate) m: u64,
-    pub(crate) a: u64,
-    pub(crate) s: u64,
+/// Values of a part, indexed like the categories of the heap.
+#[derive(Debug)]
+struct Rating(Vec<u64>);
+
+impl Deref for Rating {
+    type Target = Vec<u64>;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
 }
 
-#[derive(Debug, Clone)]
-struct Bounds {
-    pub(crate) x_lower_bound: u64,
-    pub(crate) x_upper_bound: u64,
-    pub(crate) m_lower_bound: u64,
-    pub(crate) m_upper_bound: u64,
-    pub(crate) a_lower_bound: u64,
-    pub(crate) a_upper_bound: u64,
-    pub(crate) s_lower_bound: u64,
-    pub(crate) s_upper_bound: u64,
-}
-
-impl Default for Bounds {
-    fn default() -> Self {
-        Self {
-            x_lower_bound: 1,
-            x_upper_bound: 4000,
-            m_lower_bound: 1,
-            m_upper_bound: 4000,
-            a_lower_bound: 1,
-            a_upper_bound: 4000,
-            s_lower_bound: 1,
-            s_upper_bound: 4000,
-        }
+impl Rating {
+    fn sum(&self) -> u64 {
+        self.iter().sum()
     }
 }
 
-impl Bounds {
-    fn eval_opportunities(self) -> u64 {
-        let x_range = 0.max(self.x_upper_bound - self.x_lower_bound + 1);
-        let m_range = 0.max(self.m_upper_bound - self.m_lower_bound + 1);
-        let a_range = 0.max(self.a_upper_bound - self.a_lower_bound + 1);
-        let s_range = 0.max(self.s_upper_bound - self.s_lower_bound + 1);
-        x_range * m_range * a_range * s_range
-    }
-
-    fn is_valid(&self) -> bool {
-        self.x_lower_bound < self.x_upper_bound
-            && self.m_lower_bound < self.m_upper_bound
-            && self.a_lower_bound < self.a_upper_bound
-            && self.s_lower_bound < self.s_upper_bound
-    }
-
-    fn apply_rule(&mut self, rule: &Rule, default_bound: &mut Self) -> bool {
-        let mut keep = true;
-        match rule.category {
-            Category::X => {
-                match rule.operation {
-                    Operation::Greater => {
-                        self.x_lower_bound = self.x_lower_bound.max(rule.nbr + 1);
-                        default_bound.x_upper_bound = self.x_lower_bound - 1;
-                    }
-                    Operation::Lesser => {
-                        self.x_upper_bound = self.x_upper_bound.min(rule.nbr - 1);
-                        default_bound.x_lower_bound = self.x_upper_bound + 1;
-                    }
-                }
-                if self.x_lower_bound >= self.x_upper_bound {
-                    keep = false
-                }
-            }
-            Category::M => {
-                match rule.operation {
-                    Operation::Greater => {
-                        self.m_lower_bound = self.m_lower_bound.max(rule.nbr + 1);
-                        default_bound.m_upper_bound = self.m_lower_bound - 1;
-                    }
-                    Operation::Lesser => {
-                        self.m_upper_bound = self.m_upper_bound.min(rule.nbr - 1);
-                        default_bound.m_lower_bound = self.m_upper_bound + 1;
-                    }
-                }
-                if self.m_lower_bound >= self.m_upper_bound {
-                    keep = false
-                }
-            }
-            Category::A => {
-                match rule.operation {
-                    Operation::Greater => {
-                        self.a_lower_bound = self.a_lower_bound.max(rule.nbr + 1);
-                        default_bound.a_upper_bound = self.a_lower_bound - 1;
-                    }
-                    Operation::Lesser => {
-                        self.a_upper_bound = self.a_upper_bound.min(rule.nbr - 1);
-                        default_bound.a_lower_bound = self.a_upper_bound + 1;
-                    }
-                }
-                if self.a_lower_bound >= self.a_upper_bound {
-                    keep = false
-                }
-            }
-            Category::S => {
-                match rule.operation {
-                    Operation::Greater => {
-                        self.s_lower_bound = self.s_lower_bound.max(rule.nbr + 1);
-                        default_bound.s_upper_bound = self.s_lower_bound - 1;
-                    }
-                    Operation::Lesser => {
-                        self.s_upper_bound = self.s_upper_bound.min(rule.nbr - 1);
-                        default_bound.s_lower_bound = self.s_upper_bound + 1;
-                    }
-                }
-                if self.s_lower_bound >= self.s_upper_bound {
-                    keep = false
-                }
-            }
-        }
-        keep
+/// Parsed `{name=value,...}`, before the names get turned into indices.
+fn parse_rating(value: &str) -> Vec<(&str, u64)> {
+    value
+        .trim_start_matches('{')
+        .trim_end_matches('}')
+        .split(',')
+        .map(|v| {
+            let (category, nbr) = v.split_once('=').expect("nbr should be present");
+            (category, nbr.parse().expect("Is a nbr"))
+        })
+        .collect()
+}
+
+/// Hyper-rectangle of ratings, one inclusive range per category.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Bounds(Vec<(u64, u64)>);
+
+impl Deref for Bounds {
+    type Target = Vec<(u64, u64)>;
+    fn deref(&self) -> &Self::Target {
+        &self.0
     }
 }
 
-impl From<&str> for Rating {
-    fn from(value: &str) -> Self {
-        let remove = value.replace('{', "").replace('}', "");
-        let mut split = remove.split(',');
-        let mut x = 0;
-        let mut m = 0;
-        let mut a = 0;
-        let mut s = 0;
-        while let Some(v) = split.next() {
-            let mut inner = v.split('=');
-            let target = inner.next();
-            let nbr = inner
-                .next()
-                .expect("nbr should be present")
-                .parse()
-                .expect("Is a nbr");
-            match target {
-                Some("x") => x = nbr,
-                Some("m") => m = nbr,
-                Some("a") => a = nbr,
-                Some("s") => s = nbr,
-                _ => unreachable!(),
-            };
+impl Bounds {
+    fn new(dims: usize) -> Self {
+        Self(vec![(MIN_RATING, MAX_RATING); dims])
+    }
+
+    fn eval_opportunities(&self) -> u128 {
+        self.iter().map(|(lo, hi)| (hi - lo + 1) as u128).product()
+    }
+
+    /// Same box with `axis` limited to `range`, `None` when nothing is left.
+    fn restrict(&self, axis: usize, range: (u64, u64)) -> Option<Self> {
+        let (lo, hi) = self[axis];
+        let (lo, hi) = (lo.max(range.0), hi.min(range.1));
+        if lo > hi {
+            return None;
         }
-        Self { x, m, a, s }
+        let mut ret = self.clone();
+        ret.0[axis] = (lo, hi);
+        Some(ret)
     }
-}
 
-impl Rating {
-    fn sum(&self) -> u64 {
-        self.x + self.m + self.a + self.s
+    fn intersect(&self, other: &Self) -> Option<Self> {
+        other
+            .iter()
+            .enumerate()
+            .try_fold(self.clone(), |acc, (axis, range)| {
+                acc.restrict(axis, *range)
+            })
+    }
+
+    /// Disjoint boxes covering what is in `self` but not in `other`.
+    fn subtract(&self, other: &Self) -> Vec<Self> {
+        if self.intersect(other).is_none() {
+            return vec![self.clone()];
+        }
+        let mut ret = vec![];
+        let mut rest = self.clone();
+        for (axis, (other_lo, other_hi)) in other.iter().enumerate() {
+            let (lo, hi) = rest[axis];
+            if *other_lo > lo {
+                ret.extend(rest.restrict(axis, (lo, other_lo - 1)));
+            }
+            if *other_hi < hi {
+                ret.extend(rest.restrict(axis, (other_hi + 1, hi)));
+            }
+            rest = rest
+                .restrict(axis, (*other_lo, *other_hi))
+                .expect("Should overlap");
+        }
+        ret
     }
 }
 
 struct Heap {
     pub(crate) workflows: Vec<Workflow>,
+    /// Names of the categories, in order of appearance in the ratings.
+    pub(crate) categories: Vec<String>,
     pub(crate) ratings: Vec<Rating>,
 }
 
@@ -317,119 +312,210 @@ impl From<&str> for Heap {
             .filter(|l| !l.is_empty())
             .map(|l| l.into())
             .collect();
-        let ratings = workflows_and_ratings
+        let parsed = workflows_and_ratings
             .next()
             .expect("Should have ratings")
             .lines()
             .map(|l| l.trim())
             .filter(|l| !l.is_empty())
-            .map(|l| l.into())
+            .map(parse_rating)
+            .collect::<Vec<_>>();
+        let mut categories: Vec<String> = vec![];
+        parsed.iter().flatten().for_each(|(name, _)| {
+            if !categories.iter().any(|c| c == name) {
+                categories.push(name.to_string())
+            }
+        });
+        let ratings = parsed
+            .iter()
+            .map(|values| {
+                Rating(
+                    categories
+                        .iter()
+                        .map(|c| {
+                            values
+                                .iter()
+                                .find(|(name, _)| name == c)
+                                .map(|(_, nbr)| *nbr)
+                                .expect("Should rate every category")
+                        })
+                        .collect(),
+                )
+            })
             .collect();
-        Self { workflows, ratings }
+        Self {
+            workflows,
+            categories,
+            ratings,
+        }
     }
 }
 
 impl Heap {
-    fn eval_rating(&self, current_rule: RuleDest, rating: &Rating) -> bool {
-        match current_rule {
-            RuleDest::End(EndDest::A) => true,
-            RuleDest::End(EndDest::R) => false,
-            name => {
-                let current_workflow = self
-                    .workflows
-                    .iter()
-                    .find(|w| w.name == name)
-                    .expect("Should have this workflow");
-                let new_dest = current_workflow
-                    .rules
-                    .iter()
-                    .find(|r| r.is_valid(rating))
-                    .map(|r| r.dst.clone())
-                    .unwrap_or(current_workflow.default_dest.clone());
-                self.eval_rating(new_dest, rating)
-            }
-        }
+    fn compile(&self) -> Result<Program, CompileError> {
+        Program::compile(&self.workflows, &self.categories)
     }
 
-    fn count_accepted(&self) -> u64 {
-        self.ratings
+    fn count_accepted(&self) -> Result<u64, CompileError> {
+        let program = self.compile()?;
+        Ok(self
+            .ratings
             .iter()
-            .filter_map(|r| {
-                if self.eval_rating(Default::default(), r) {
-                    Some(r.sum())
-                } else {
-                    None
-                }
-            })
-            .sum()
-    }
-
-    fn explore_path(
-        &self,
-        current: &RuleDest,
-        bound: Bounds,
-        mut path: Vec<RuleDest>,
-    ) -> Vec<Bounds> {
-        if current.is_end() {
-            if current.is_approved() {
-                vec![bound]
-            } else {
-                vec![]
-            }
-        } else {
-            let curr = self
-                .workflows
+            .filter(|r| program.is_accepted(r))
+            .map(|r| r.sum())
+            .sum())
+    }
+
+    fn rating_text(&self, rating: &Rating) -> String {
+        let values = self
+            .categories
+            .iter()
+            .zip(rating.iter())
+            .map(|(c, v)| format!("{c}={v}"))
+            .collect::<Vec<_>>();
+        format!("{{{}}}", values.join(","))
+    }
+
+    /// One line per part with the workflows it went through, then how many
+    /// parts left through each rule sending to `A` or `R`.
+    fn explain(&self) -> Result<String, CompileError> {
+        let program = self.compile()?;
+        let mut ret = String::new();
+        let mut exits: BTreeMap<(bool, String), usize> = BTreeMap::new();
+        for rating in self.ratings.iter() {
+            let mut steps = vec![];
+            let accepted = program.run(rating, |workflow, rule| {
+                steps.push(program.step_text(workflow, rule))
+            });
+            let verdict = if accepted { "A" } else { "R" };
+            ret.push_str(&format!(
+                "{} : {} -> {}\n",
+                self.rating_text(rating),
+                steps.join(" -> "),
+                verdict
+            ));
+            let last = steps.pop().expect("Should visit at least `in`");
+            *exits.entry((accepted, last)).or_default() += 1;
+        }
+        for accepted in [true, false] {
+            let verdict = if accepted { "A" } else { "R" };
+            let total: usize = exits
                 .iter()
-                .find(|w| &w.name == current)
-                .expect("Should have this workflow");
-            let mut default_bound = bound.clone();
-            let mut ret = vec![];
-            for r in curr.rules.iter() {
-                let mut new_bound = default_bound.clone();
-                if new_bound.apply_rule(r, &mut default_bound) {
-                    let mut new_path = path.clone();
-                    new_path.push(r.dst.clone());
-                    ret.append(&mut self.explore_path(&r.dst, new_bound, new_path));
-                };
-            }
-            if default_bound.is_valid() {
-                path.push(curr.default_dest.clone());
-                ret.append(&mut self.explore_path(&curr.default_dest, default_bound, path));
-            }
-            ret
+                .filter(|((a, _), _)| *a == accepted)
+                .map(|(_, n)| n)
+                .sum();
+            ret.push_str(&format!("{} : {} parts\n", verdict, total));
+            exits
+                .iter()
+                .filter(|((a, _), _)| *a == accepted)
+                .for_each(|((_, step), n)| ret.push_str(&format!("  {} : {}\n", step, n)));
         }
+        Ok(ret)
     }
 
-    fn explore_paths(&self) -> u64 {
-        let current = RuleDest::OtherRule(String::from("in"));
-        let bound = Bounds::default();
-        let ret = self.explore_path(&current, bound, vec![current.clone()]);
-        ret.into_iter()
-            .fold(0, |acc, r| acc + r.eval_opportunities())
+    fn analyze(&self) -> Result<String, CompileError> {
+        let program = self.compile()?;
+        Ok(Analysis::from(&program).to_string())
+    }
+
+    /// Same file with the workflows minimized.
+    fn minimized(&self) -> Result<String, CompileError> {
+        let program = self.compile()?;
+        let ratings = self
+            .ratings
+            .iter()
+            .map(|r| self.rating_text(r))
+            .collect::<Vec<_>>();
+        Ok(format!(
+            "{}\n\n{}\n",
+            Analysis::from(&program).minimize(),
+            ratings.join("\n")
+        ))
+    }
+
+    fn export_regions(&self, csv: bool) -> Result<String, CompileError> {
+        let regions = self.compile()?.accepted_bounds();
+        Ok(if csv {
+            export::regions_csv(&self.categories, &regions)
+        } else {
+            export::regions_json(&self.categories, &regions)
+        })
+    }
+
+    fn explore_paths(&self) -> Result<u128, CompileError> {
+        let program = self.compile()?;
+        Ok(program
+            .accepted_bounds()
+            .into_iter()
+            .fold(0, |acc, r| acc + r.eval_opportunities()))
     }
 }
 
-pub(crate) fn eval_file(file: &str) -> u64 {
+pub(crate) fn eval_file(file: &str) -> Result<u64, CompileError> {
     let heap: Heap = file.into();
     heap.count_accepted()
 }
 
-pub(crate) fn eval_file_2(file: &str) -> u64 {
+pub(crate) fn eval_file_2(file: &str) -> Result<u128, CompileError> {
     let heap: Heap = file.into();
-    let ret = heap.explore_paths();
-    ret
+    heap.explore_paths()
 }
 
+fn print_res<T: Display>(res: Result<T, CompileError>) {
+    match res {
+        Ok(res) => print!("res : {}", res),
+        Err(e) => print!("invalid workflows : {}", e),
+    }
+}
+pub(crate) fn print_explain(file: &str) {
+    let heap: Heap = file.into();
+    match heap.explain() {
+        Ok(explanation) => print!("{}", explanation),
+        Err(e) => println!("invalid workflows : {}", e),
+    }
+    print_res(heap.count_accepted());
+}
+pub(crate) fn print_analysis(file: &str) {
+    let heap: Heap = file.into();
+    match heap.analyze() {
+        Ok(analysis) => print!("{}", analysis),
+        Err(e) => print!("invalid workflows : {}", e),
+    }
+}
+pub(crate) fn print_minimized(file: &str) {
+    let heap: Heap = file.into();
+    match heap.minimized() {
+        Ok(minimized) => print!("{}", minimized),
+        Err(e) => print!("invalid workflows : {}", e),
+    }
+}
+/// Accepted regions as JSON or, with `csv`, as CSV.
+pub(crate) fn print_regions(file: &str, csv: bool) {
+    let heap: Heap = file.into();
+    match heap.export_regions(csv) {
+        Ok(regions) => print!("{}", regions),
+        Err(e) => print!("invalid workflows : {}", e),
+    }
+}
+pub(crate) fn print_dot(file: &str) {
+    let heap: Heap = file.into();
+    match heap.compile() {
+        Ok(program) => print!("{}", export::workflows_dot(&program)),
+        Err(e) => print!("invalid workflows : {}", e),
+    }
+}
 pub(crate) fn print_sol_1(file: &str) {
-    print!("res : {}", eval_file(file));
+    print_res(eval_file(file));
 }
 pub(crate) fn print_sol_2(file: &str) {
-    print!("res : {}", eval_file_2(file));
+    print_res(eval_file_2(file));
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{eval_file, eval_file_2};
+    use super::{
+        eval_file, eval_file_2, export, program::CompileError, Bounds, Comparison, Heap, Operation,
+    };
 
     fn data() -> &'static str {
         r#"px{a<2006:qkq,m>2090:A,rfg}
@@ -452,10 +538,129 @@ hdj{m>838:A,pv}
     }
     #[test]
     fn test_0() {
-        assert_eq!(19114, eval_file(data()));
+        assert_eq!(Ok(19114), eval_file(data()));
     }
     #[test]
     fn test_1() {
-        assert_eq!(167409079868000, eval_file_2(data()));
+        assert_eq!(Ok(167409079868000), eval_file_2(data()));
+    }
+    fn data_2() -> &'static str {
+        r#"in{cool>=3990&shiny!=1:A,cool==5:hot,R}
+hot{shiny<=10:R,A}
+
+{cool=3995,shiny=1}
+{cool=5,shiny=7}
+{cool=5,shiny=70}
+{cool=4000,shiny=2}"#
+    }
+    #[test]
+    fn test_2() {
+        assert_eq!(
+            Comparison {
+                category: "shiny".to_string(),
+                operation: Operation::NotEqual,
+                nbr: 1
+            },
+            Comparison::from("shiny!=1")
+        );
+        assert_eq!(Ok(75 + 4002), eval_file(data_2()));
+        // 11 * 3999 from the first rule, then 3990 values of shiny above 10.
+        assert_eq!(Ok(11 * 3999 + 3990), eval_file_2(data_2()));
+        assert_eq!(
+            Err(CompileError::UnknownCategory {
+                from: "in".to_string(),
+                category: "x".to_string()
+            }),
+            eval_file("in{x>1:A,R}\n\n{cool=1}")
+        );
+    }
+    #[test]
+    fn test_3() {
+        let outer = Bounds(vec![(1, 10), (1, 10)]);
+        let inner = Bounds(vec![(3, 4), (5, 20)]);
+        let rest = outer.subtract(&inner);
+        assert_eq!(
+            outer.eval_opportunities(),
+            rest.iter().map(|b| b.eval_opportunities()).sum::<u128>() + 12
+        );
+        assert!(rest.iter().all(|b| b.intersect(&inner).is_none()));
+    }
+    #[test]
+    fn test_4() {
+        let heap: Heap = data().into();
+        let explanation = heap.explain().expect("Should compile");
+        let mut lines = explanation.lines();
+        assert_eq!(
+            Some("{x=787,m=2655,a=1222,s=2876} : in (default) -> qqz (s>2770) -> qs (default) -> lnx (m>1548) -> A"),
+            lines.next()
+        );
+        assert!(explanation.contains("A : 3 parts\n"));
+        assert!(explanation.contains("R : 2 parts\n"));
+        assert!(explanation.contains("  lnx (m>1548) : 1\n"));
+    }
+    #[test]
+    fn test_5() {
+        let heap: Heap = data().into();
+        let analysis = heap.analyze().expect("Should compile");
+        assert!(analysis.contains("lnx -> A"));
+        assert!(analysis.contains("gd -> R"));
+        let minimized = heap.minimized().expect("Should compile");
+        assert!(!minimized.contains("lnx{"));
+        assert!(!minimized.contains("gd{"));
+        assert_eq!(eval_file(data()), eval_file(&minimized));
+        assert_eq!(eval_file_2(data()), eval_file_2(&minimized));
+    }
+    #[test]
+    fn test_6() {
+        let heap: Heap = "in{x>10:px,x>20:R,A}\npx{x<5:R,A}\nqq{R}\n\n{x=1}".into();
+        let analysis = heap.analyze().expect("Should compile");
+        assert_eq!(
+            "dead rules :\n  in rule 1 (x>20:R)\n  px rule 0 (x<5:R)\nunreachable workflows : qq\nconstant workflows : in -> A, px -> A",
+            analysis
+        );
+        assert_eq!(
+            "in{A}\n\n{x=1}\n",
+            heap.minimized().expect("Should compile")
+        );
+    }
+    #[test]
+    fn test_7() {
+        let heap: Heap = data().into();
+        let csv = heap.export_regions(true).expect("Should compile");
+        let total = csv
+            .lines()
+            .skip(1)
+            .map(|l| l.rsplit(',').next().unwrap().parse::<u128>().unwrap())
+            .sum::<u128>();
+        assert_eq!(Ok(total), eval_file_2(data()));
+        let json = heap.export_regions(false).expect("Should compile");
+        assert!(json.ends_with("\"combinations\": 167409079868000\n}\n"));
+        let dot = export::workflows_dot(&heap.compile().expect("Should compile"));
+        assert!(dot.contains("  \"in\" -> \"px\" [label=\"s<1351\"];\n"));
+        assert!(dot.contains("  \"in\" -> \"qqz\" [style=dashed];\n"));
+    }
+    fn data_3() -> &'static str {
+        r#"in{x>10:px,A}
+px{x<5:in,R}
+
+{x=1,m=2,a=3,s=0}
+{x=20,m=2,a=3,s=0}"#
+    }
+    #[test]
+    fn test_8() {
+        // `px` can send parts back to `in`, but none of the ones it receives.
+        assert_eq!(Ok(6), eval_file(data_3()));
+        assert_eq!(Ok(10 * 4000 * 4000 * 4000), eval_file_2(data_3()));
+    }
+    #[test]
+    fn test_9() {
+        // `a` and `b` send parts to each other, each for parts the other one
+        // never receives.
+        let heap: Heap = "in{x<5:a,x<10:b,R}\na{m<5:b,R}\nb{m>10:a,R}\n\n{x=1,m=1}".into();
+        assert_eq!(Ok(0), heap.explore_paths());
+        let analysis = heap.analyze().expect("Should compile");
+        assert!(analysis.starts_with("dead rules :\nunreachable workflows : \n"));
+        let minimized = heap.minimized().expect("Should compile");
+        assert_eq!(Ok(0), eval_file_2(&minimized));
     }
 }
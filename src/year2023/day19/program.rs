@@ -0,0 +1,331 @@
+use std::{collections::HashMap, fmt::Display};
+
+use super::{matches, matching_bounds, Bounds, Comparison, EndDest, Rating, RuleDest, Workflow};
+
+const START: &str = "in";
+
+/// Where a part goes once a rule matched, workflows being referred to by index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Target {
+    Accept,
+    Reject,
+    Workflow(usize),
+}
+
+#[derive(Debug)]
+pub(crate) struct CompiledWorkflow {
+    pub(crate) rules: Vec<(Vec<Comparison<usize>>, Target)>,
+    pub(crate) default: Target,
+}
+
+/// Workflows with every destination and category resolved, `names[i]` being
+/// the name of `workflows[i]`.
+#[derive(Debug)]
+pub(crate) struct Program {
+    pub(crate) names: Vec<String>,
+    pub(crate) categories: Vec<String>,
+    pub(crate) workflows: Vec<CompiledWorkflow>,
+    pub(crate) start: usize,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum CompileError {
+    /// No workflow named `in`.
+    MissingStart,
+    /// Two workflows sharing the same name.
+    DuplicateWorkflow { name: String },
+    /// A rule of `from` sending parts to a workflow that does not exist.
+    UnknownWorkflow { from: String, name: String },
+    /// A rule of `from` comparing a category no rating declares.
+    UnknownCategory { from: String, category: String },
+    /// Workflows reachable from `in` sending parts to each other forever.
+    Cycle { workflows: Vec<String> },
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingStart => write!(f, "no workflow named {START}"),
+            Self::DuplicateWorkflow { name } => write!(f, "workflow {name} is defined twice"),
+            Self::UnknownWorkflow { from, name } => {
+                write!(f, "workflow {from} refers to unknown workflow {name}")
+            }
+            Self::UnknownCategory { from, category } => {
+                write!(f, "workflow {from} refers to unknown category {category}")
+            }
+            Self::Cycle { workflows } => write!(f, "cycle {}", workflows.join(" -> ")),
+        }
+    }
+}
+
+impl Program {
+    pub(crate) fn compile(value: &[Workflow], categories: &[String]) -> Result<Self, CompileError> {
+        let mut indices = HashMap::new();
+        for (i, w) in value.iter().enumerate() {
+            if indices.insert(w.name.as_str(), i).is_some() {
+                return Err(CompileError::DuplicateWorkflow {
+                    name: w.name.clone(),
+                });
+            }
+        }
+        let start = *indices.get(START).ok_or(CompileError::MissingStart)?;
+
+        let resolve = |from: &str, dest: &RuleDest| match dest {
+            RuleDest::End(EndDest::A) => Ok(Target::Accept),
+            RuleDest::End(EndDest::R) => Ok(Target::Reject),
+            RuleDest::OtherRule(name) => indices
+                .get(name.as_str())
+                .map(|i| Target::Workflow(*i))
+                .ok_or_else(|| CompileError::UnknownWorkflow {
+                    from: from.to_string(),
+                    name: name.clone(),
+                }),
+        };
+        let resolve_comparison = |from: &str, c: &Comparison<String>| {
+            let category = categories
+                .iter()
+                .position(|name| name == &c.category)
+                .ok_or_else(|| CompileError::UnknownCategory {
+                    from: from.to_string(),
+                    category: c.category.clone(),
+                })?;
+            Ok(Comparison {
+                category,
+                operation: c.operation,
+                nbr: c.nbr,
+            })
+        };
+        let workflows = value
+            .iter()
+            .map(|w| {
+                Ok(CompiledWorkflow {
+                    rules: w
+                        .rules
+                        .iter()
+                        .map(|r| {
+                            let conditions = r
+                                .conditions
+                                .iter()
+                                .map(|c| resolve_comparison(&w.name, c))
+                                .collect::<Result<_, CompileError>>()?;
+                            Ok((conditions, resolve(&w.name, &r.dst)?))
+                        })
+                        .collect::<Result<_, CompileError>>()?,
+                    default: resolve(&w.name, &w.default_dest)?,
+                })
+            })
+            .collect::<Result<_, CompileError>>()?;
+
+        let program = Self {
+            names: value.iter().map(|w| w.name.clone()).collect(),
+            categories: categories.to_vec(),
+            workflows,
+            start,
+        };
+        match program.find_cycle() {
+            Some(cycle) => Err(CompileError::Cycle {
+                workflows: cycle
+                    .into_iter()
+                    .map(|i| program.names[i].clone())
+                    .collect(),
+            }),
+            None => Ok(program),
+        }
+    }
+
+    /// Workflows some ratings go through forever, the first one being repeated
+    /// at the end. Loops in the rules no rating can follow are fine.
+    fn find_cycle(&self) -> Option<Vec<usize>> {
+        self.explore(
+            Target::Workflow(self.start),
+            Bounds::new(self.categories.len()),
+            &mut vec![],
+            &mut |_, _, _| (),
+            &mut vec![],
+        )
+        .err()
+    }
+
+    /// Follows `rating` from `in`, calling `on_step` with every workflow visited
+    /// and the rule that matched in it, `None` when the default destination fired.
+    pub(crate) fn run<F: FnMut(usize, Option<usize>)>(
+        &self,
+        rating: &Rating,
+        mut on_step: F,
+    ) -> bool {
+        let mut current = Target::Workflow(self.start);
+        loop {
+            match current {
+                Target::Accept => return true,
+                Target::Reject => return false,
+                Target::Workflow(idx) => {
+                    let workflow = &self.workflows[idx];
+                    let matched = workflow
+                        .rules
+                        .iter()
+                        .position(|(conditions, _)| matches(conditions, rating));
+                    on_step(idx, matched);
+                    current = matched.map_or(workflow.default, |i| workflow.rules[i].1);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn is_accepted(&self, rating: &Rating) -> bool {
+        self.run(rating, |_, _| ())
+    }
+
+    /// `x>10&m<5` as written in the workflows file.
+    pub(crate) fn condition_text(&self, conditions: &[Comparison<usize>]) -> String {
+        conditions
+            .iter()
+            .map(|c| {
+                format!(
+                    "{}{}{}",
+                    self.categories[c.category],
+                    c.operation.symbol(),
+                    c.nbr
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    /// `name (matched condition)` for a step given by `run`.
+    pub(crate) fn step_text(&self, workflow: usize, rule: Option<usize>) -> String {
+        let reason = match rule {
+            Some(i) => self.condition_text(&self.workflows[workflow].rules[i].0),
+            None => String::from("default"),
+        };
+        format!("{} ({})", self.names[workflow], reason)
+    }
+
+    /// `path` holding the workflows `bound` went through, a region coming back
+    /// to one of them being sent around the returned cycle forever.
+    fn explore<F: FnMut(usize, Option<usize>, &Bounds)>(
+        &self,
+        target: Target,
+        bound: Bounds,
+        path: &mut Vec<usize>,
+        on_fire: &mut F,
+        ret: &mut Vec<Bounds>,
+    ) -> Result<(), Vec<usize>> {
+        match target {
+            Target::Accept => ret.push(bound),
+            Target::Reject => (),
+            Target::Workflow(idx) => {
+                if let Some(from) = path.iter().position(|w| *w == idx) {
+                    let mut cycle = path[from..].to_vec();
+                    cycle.push(idx);
+                    return Err(cycle);
+                }
+                path.push(idx);
+                let workflow = &self.workflows[idx];
+                let mut remaining = vec![bound];
+                for (i, (conditions, target)) in workflow.rules.iter().enumerate() {
+                    for matching in matching_bounds(conditions, self.categories.len()) {
+                        let mut rest = vec![];
+                        for b in remaining {
+                            if let Some(inside) = b.intersect(&matching) {
+                                on_fire(idx, Some(i), &inside);
+                                self.explore(*target, inside, path, on_fire, ret)?;
+                            }
+                            rest.extend(b.subtract(&matching));
+                        }
+                        remaining = rest;
+                    }
+                }
+                for b in remaining {
+                    on_fire(idx, None, &b);
+                    self.explore(workflow.default, b, path, on_fire, ret)?;
+                }
+                path.pop();
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends every possible rating through the workflows, calling `on_fire`
+    /// with each non empty region leaving a workflow through a rule, `None`
+    /// being the default destination. Returns the disjoint accepted regions.
+    pub(crate) fn propagate<F: FnMut(usize, Option<usize>, &Bounds)>(
+        &self,
+        mut on_fire: F,
+    ) -> Vec<Bounds> {
+        let mut ret = vec![];
+        self.explore(
+            Target::Workflow(self.start),
+            Bounds::new(self.categories.len()),
+            &mut vec![],
+            &mut on_fire,
+            &mut ret,
+        )
+        .expect("Should have no cycle once compiled");
+        ret
+    }
+
+    /// Disjoint ranges of ratings ending up accepted.
+    pub(crate) fn accepted_bounds(&self) -> Vec<Bounds> {
+        self.propagate(|_, _, _| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CompileError, Program, Target, Workflow};
+
+    fn compile(workflows: &str) -> Result<Program, CompileError> {
+        let workflows = workflows
+            .lines()
+            .map(|l| l.into())
+            .collect::<Vec<Workflow>>();
+        let categories = ["x", "m", "a", "s"].map(String::from);
+        Program::compile(&workflows, &categories)
+    }
+    #[test]
+    fn test_0() {
+        let program = compile("px{a<2006:qkq,R}\nin{s<1351:px,A}\nqkq{A}").expect("Should compile");
+        assert_eq!(1, program.start);
+        assert_eq!(Target::Workflow(2), program.workflows[0].rules[0].1);
+        assert_eq!(Target::Accept, program.workflows[1].default);
+    }
+    #[test]
+    fn test_1() {
+        assert_eq!(
+            Err(CompileError::MissingStart),
+            compile("px{A}").map(|_| ())
+        );
+        assert_eq!(
+            Err(CompileError::UnknownWorkflow {
+                from: "in".to_string(),
+                name: "qs".to_string()
+            }),
+            compile("in{x>10:qs,A}").map(|_| ())
+        );
+        assert_eq!(
+            Err(CompileError::DuplicateWorkflow {
+                name: "in".to_string()
+            }),
+            compile("in{A}\nin{R}").map(|_| ())
+        );
+    }
+    #[test]
+    fn test_2() {
+        assert_eq!(
+            Err(CompileError::Cycle {
+                workflows: vec!["px".to_string(), "qs".to_string(), "px".to_string()]
+            }),
+            compile("in{x>10:px,A}\npx{m<5:qs,R}\nqs{px}").map(|_| ())
+        );
+        // A cycle out of reach of `in` never runs.
+        assert!(compile("in{A}\npx{qs}\nqs{px}").is_ok());
+        // Neither does one no rating can go around.
+        assert!(compile("in{x>10:px,A}\npx{x<5:in,R}").is_ok());
+        assert_eq!(
+            Err(CompileError::Cycle {
+                workflows: vec!["in".to_string(), "px".to_string(), "in".to_string()]
+            }),
+            compile("in{x>10:px,A}\npx{x<50:in,R}").map(|_| ())
+        );
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::cycle;
+
+const NBR_PRESSES: usize = 1000;
+/// Presses simulated before giving up on finding the cycles feeding `rx`.
+const MAX_PRESSES: u64 = 1_000_000;
+const BROADCASTER: &str = "broadcaster";
+const RX: &str = "rx";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pulse {
+    Low,
+    High,
+}
+
+#[derive(Debug)]
+enum ModuleKind {
+    Broadcaster,
+    FlipFlop {
+        on: bool,
+    },
+    /// `memory[i]` being the last pulse received from `inputs[i]`.
+    Conjunction {
+        inputs: Vec<usize>,
+        memory: Vec<Pulse>,
+    },
+    /// Module only receiving pulses, like `output` or `rx`.
+    Untyped,
+}
+
+#[derive(Debug)]
+struct Module {
+    pub(crate) name: String,
+    pub(crate) kind: ModuleKind,
+    pub(crate) outputs: Vec<usize>,
+}
+
+impl Module {
+    /// Pulse sent to every output when receiving `pulse` from `from`, if any.
+    fn receive(&mut self, from: Option<usize>, pulse: Pulse) -> Option<Pulse> {
+        match &mut self.kind {
+            ModuleKind::Broadcaster => Some(pulse),
+            ModuleKind::FlipFlop { .. } if pulse == Pulse::High => None,
+            ModuleKind::FlipFlop { on } => {
+                *on = !*on;
+                Some(if *on { Pulse::High } else { Pulse::Low })
+            }
+            ModuleKind::Conjunction { inputs, memory } => {
+                let from = from.expect("Should not be pressed");
+                let idx = inputs
+                    .iter()
+                    .position(|i| *i == from)
+                    .expect("Should be an input");
+                memory[idx] = pulse;
+                if memory.iter().all(|p| *p == Pulse::High) {
+                    Some(Pulse::Low)
+                } else {
+                    Some(Pulse::High)
+                }
+            }
+            ModuleKind::Untyped => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Network {
+    pub(crate) modules: Vec<Module>,
+    pub(crate) broadcaster: usize,
+}
+
+impl From<&str> for Network {
+    fn from(value: &str) -> Self {
+        let lines = value
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .map(|l| l.split_once(" -> ").expect("Should have outputs"))
+            .collect::<Vec<_>>();
+
+        let mut indices: HashMap<&str, usize> = HashMap::new();
+        let mut modules = vec![];
+        for (module, _) in lines.iter() {
+            let (kind, name) = match module.chars().next() {
+                Some('%') => (ModuleKind::FlipFlop { on: false }, &module[1..]),
+                Some('&') => (
+                    ModuleKind::Conjunction {
+                        inputs: vec![],
+                        memory: vec![],
+                    },
+                    &module[1..],
+                ),
+                _ if *module == BROADCASTER => (ModuleKind::Broadcaster, *module),
+                _ => unreachable!(),
+            };
+            indices.insert(name, modules.len());
+            modules.push(Module {
+                name: name.to_string(),
+                kind,
+                outputs: vec![],
+            });
+        }
+        for (i, (_, outputs)) in lines.iter().enumerate() {
+            for output in outputs.split(", ") {
+                let idx = *indices.entry(output).or_insert_with(|| {
+                    modules.push(Module {
+                        name: output.to_string(),
+                        kind: ModuleKind::Untyped,
+                        outputs: vec![],
+                    });
+                    modules.len() - 1
+                });
+                modules[i].outputs.push(idx);
+                if let ModuleKind::Conjunction { inputs, memory } = &mut modules[idx].kind {
+                    inputs.push(i);
+                    memory.push(Pulse::Low);
+                }
+            }
+        }
+        let broadcaster = *indices.get(BROADCASTER).expect("Should have a broadcaster");
+        Self {
+            modules,
+            broadcaster,
+        }
+    }
+}
+
+impl Network {
+    fn index(&self, name: &str) -> Option<usize> {
+        self.modules.iter().position(|m| m.name == name)
+    }
+
+    /// Presses the button once, calling `on_pulse(from, to, pulse)` for every
+    /// pulse in the order they are processed, `from` being `None` for the button.
+    fn press<F: FnMut(Option<usize>, usize, Pulse)>(&mut self, mut on_pulse: F) {
+        let mut queue = VecDeque::from([(None, self.broadcaster, Pulse::Low)]);
+        while let Some((from, to, pulse)) = queue.pop_front() {
+            on_pulse(from, to, pulse);
+            if let Some(sent) = self.modules[to].receive(from, pulse) {
+                self.modules[to]
+                    .outputs
+                    .iter()
+                    .for_each(|o| queue.push_back((Some(to), *o, sent)));
+            }
+        }
+    }
+
+    fn count_pulses(&mut self, nbr_presses: usize) -> (usize, usize) {
+        let mut low = 0;
+        let mut high = 0;
+        (0..nbr_presses).for_each(|_| {
+            self.press(|_, _, pulse| match pulse {
+                Pulse::Low => low += 1,
+                Pulse::High => high += 1,
+            })
+        });
+        (low, high)
+    }
+
+    /// Presses until `target` receives a low pulse, without any shortcut.
+    fn presses_until_low(&mut self, target: usize, max_presses: u64) -> Option<u64> {
+        (1..=max_presses).find(|_| {
+            let mut received = false;
+            self.press(|_, to, pulse| received |= to == target && pulse == Pulse::Low);
+            received
+        })
+    }
+
+    /// `rx` receiving a single low pulse once every input of the conjunction
+    /// feeding it sent a high pulse during the same press. Each of these inputs
+    /// is expected to send a high pulse once per cycle, so the answer is the
+    /// first press matching every `(first high, period)`.
+    fn presses_until_rx(&mut self) -> Option<u64> {
+        let rx = self.index(RX)?;
+        let feeder = self.modules.iter().position(|m| m.outputs.contains(&rx))?;
+        let inputs = match &self.modules[feeder].kind {
+            ModuleKind::Conjunction { inputs, .. } => inputs.clone(),
+            _ => return None,
+        };
+        let mut highs: Vec<Vec<u64>> = vec![vec![]; inputs.len()];
+        for press in 1..=MAX_PRESSES {
+            self.press(|from, to, pulse| {
+                if to == feeder && pulse == Pulse::High {
+                    let idx = inputs
+                        .iter()
+                        .position(|i| Some(*i) == from)
+                        .expect("Should be an input");
+                    if highs[idx].last() != Some(&press) {
+                        highs[idx].push(press);
+                    }
+                }
+            });
+            if highs.iter().all(|h| h.len() >= 2) {
+                let cycles = highs.iter().map(|h| (h[0], h[1] - h[0]));
+                return first_common_press(cycles);
+            }
+        }
+        None
+    }
+}
+
+/// Smallest press `p` with `p = first + k * period` for every cycle, through
+/// the chinese remainder theorem.
+fn first_common_press<I: Iterator<Item = (u64, u64)>>(mut cycles: I) -> Option<u64> {
+    let mut latest = 0;
+    let (remainder, modulus) = cycles.try_fold((0, 1), |acc, (first, period)| {
+        latest = latest.max(first as usize);
+        cycle::merge_congruences(acc, ((first % period) as usize, period as usize))
+    })?;
+    Some((remainder + latest.saturating_sub(remainder).div_ceil(modulus) * modulus) as u64)
+}
+
+pub(crate) fn eval_file(file: &str) -> usize {
+    let mut network: Network = file.into();
+    let (low, high) = network.count_pulses(NBR_PRESSES);
+    low * high
+}
+
+/// Cycle analysis when `rx` is fed by a conjunction, plain simulation otherwise.
+pub(crate) fn eval_file_2(file: &str) -> Option<u64> {
+    let mut network: Network = file.into();
+    let rx = network.index(RX)?;
+    network.presses_until_rx().or_else(|| {
+        let mut network: Network = file.into();
+        network.presses_until_low(rx, MAX_PRESSES)
+    })
+}
+pub(crate) fn print_sol_1(file: &str) {
+    print!("res : {}", eval_file(file));
+}
+pub(crate) fn print_sol_2(file: &str) {
+    match eval_file_2(file) {
+        Some(res) => print!("res : {}", res),
+        None => print!("rx never receives a low pulse"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{eval_file, eval_file_2, first_common_press, Network, MAX_PRESSES};
+
+    fn data() -> &'static str {
+        r#"
+broadcaster -> a, b, c
+%a -> b
+%b -> c
+%c -> inv
+&inv -> a"#
+    }
+    fn data_2() -> &'static str {
+        r#"
+broadcaster -> a
+%a -> inv, con
+&inv -> b
+%b -> con
+&con -> output"#
+    }
+    /// Two counters, the first one sending a high pulse to `hub` every 4
+    /// presses starting on the 3rd, the second one every 8 starting on the 7th.
+    fn data_3() -> &'static str {
+        r#"
+broadcaster -> a1, b1
+%a1 -> a2, ca
+%a2 -> ca
+&ca -> ia
+&ia -> hub
+%b1 -> b2, cb
+%b2 -> b3, cb
+%b3 -> cb
+&cb -> ib
+&ib -> hub
+&hub -> rx"#
+    }
+    #[test]
+    fn test_0() {
+        assert_eq!(32000000, eval_file(data()));
+        assert_eq!(11687500, eval_file(data_2()));
+    }
+    #[test]
+    fn test_1() {
+        assert_eq!(Some(7), eval_file_2(data_3()));
+        let mut network: Network = data_3().into();
+        let rx = network.index("rx").expect("Should have rx");
+        assert_eq!(Some(7), network.presses_until_low(rx, MAX_PRESSES));
+        assert_eq!(None, eval_file_2(data()));
+    }
+    #[test]
+    fn test_2() {
+        assert_eq!(
+            Some(3 * 5 * 7),
+            first_common_press([(3, 3), (5, 5), (7, 7)].into_iter())
+        );
+        assert_eq!(Some(11), first_common_press([(3, 4), (5, 6)].into_iter()));
+        assert_eq!(None, first_common_press([(1, 2), (2, 4)].into_iter()));
+    }
+}
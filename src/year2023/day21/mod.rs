@@ -0,0 +1,222 @@
+use num::integer::lcm;
+
+const NBR_STEPS: usize = 64;
+const NBR_STEPS_INFINITE: usize = 26501365;
+/// Periods sampled before checking that the growth became quadratic.
+const MIN_PERIODS: usize = 8;
+/// The BFS covering a square of `2 * periods` gardens on each side, giving up
+/// past this keeps it within a few hundred megabytes on real inputs.
+const MAX_PERIODS: usize = 32;
+/// Consecutive equal second differences needed to trust the extrapolation.
+const STABLE_DIFFS: usize = 3;
+
+struct Garden {
+    pub(crate) rocks: Vec<Vec<bool>>,
+    pub(crate) height: usize,
+    pub(crate) width: usize,
+    pub(crate) start: (usize, usize),
+}
+
+impl From<&str> for Garden {
+    fn from(value: &str) -> Self {
+        let lines = value
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .collect::<Vec<_>>();
+        let start = lines
+            .iter()
+            .enumerate()
+            .find_map(|(x, l)| l.find('S').map(|y| (x, y)))
+            .expect("Should have a starting plot");
+        let rocks = lines
+            .iter()
+            .map(|l| l.chars().map(|c| c == '#').collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        Self {
+            height: rocks.len(),
+            width: rocks[0].len(),
+            rocks,
+            start,
+        }
+    }
+}
+
+/// `counts[d]` being the number of plots at a distance `d` from the start.
+struct Reach(Vec<usize>);
+
+impl Reach {
+    /// A plot reached in `d` steps can be reached again in `d + 2` by going
+    /// back and forth, so only plots of the same parity as `steps` count.
+    fn plots(&self, steps: usize) -> usize {
+        self.0
+            .iter()
+            .take(steps + 1)
+            .skip(steps % 2)
+            .step_by(2)
+            .sum()
+    }
+}
+
+impl Garden {
+    fn is_rock(&self, x: i64, y: i64) -> bool {
+        self.rocks[x.rem_euclid(self.height as i64) as usize]
+            [y.rem_euclid(self.width as i64) as usize]
+    }
+
+    /// Layered BFS from the start up to `max_steps`, on the garden alone or on
+    /// as many copies of it as the steps can reach when `infinite`.
+    fn reach(&self, max_steps: usize, infinite: bool) -> Reach {
+        let (sx, sy) = (self.start.0 as i64, self.start.1 as i64);
+        let radius = max_steps as i64;
+        let side = 2 * radius + 1;
+        let inside = |x: i64, y: i64| {
+            (x - sx).abs() <= radius
+                && (y - sy).abs() <= radius
+                && (infinite
+                    || (0 <= x && x < self.height as i64 && 0 <= y && y < self.width as i64))
+        };
+        let idx = |x: i64, y: i64| ((x - sx + radius) * side + (y - sy + radius)) as usize;
+
+        let mut visited = vec![false; (side * side) as usize];
+        visited[idx(sx, sy)] = true;
+        let mut frontier = vec![(sx, sy)];
+        let mut counts = vec![];
+        while !frontier.is_empty() && counts.len() <= max_steps {
+            counts.push(frontier.len());
+            let mut next = vec![];
+            for (x, y) in frontier {
+                for (dx, dy) in [(0, 1), (1, 0), (0, -1), (-1, 0)] {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if inside(nx, ny) && !self.is_rock(nx, ny) && !visited[idx(nx, ny)] {
+                        visited[idx(nx, ny)] = true;
+                        next.push((nx, ny));
+                    }
+                }
+            }
+            frontier = next;
+        }
+        Reach(counts)
+    }
+
+    /// Once the steps cover enough copies of the garden, the plots reached
+    /// after `rem + k * period` steps grow quadratically with `k`.
+    fn reachable_infinite(&self, steps: usize, max_periods: usize) -> Result<usize, String> {
+        let period = lcm(self.height, self.width);
+        let (target, rem) = (steps / period, steps % period);
+        let mut nbr_periods = MIN_PERIODS;
+        loop {
+            let reach = self.reach(rem + nbr_periods * period, true);
+            let samples = (0..=nbr_periods)
+                .map(|k| reach.plots(rem + k * period) as i64)
+                .collect::<Vec<_>>();
+            if target <= nbr_periods {
+                return Ok(samples[target] as usize);
+            }
+            let diffs = samples
+                .windows(3)
+                .map(|w| w[2] - 2 * w[1] + w[0])
+                .collect::<Vec<_>>();
+            let last = &diffs[diffs.len() - STABLE_DIFFS..];
+            if last.iter().all(|d| *d == last[0]) {
+                return Ok(extrapolate(&samples, target) as usize);
+            }
+            if nbr_periods * 2 > max_periods {
+                return Err(format!(
+                    "no quadratic growth after {nbr_periods} copies of the garden"
+                ));
+            }
+            nbr_periods *= 2;
+        }
+    }
+}
+
+/// Value at index `target` of a sequence whose second difference stays the
+/// same after its last known values.
+fn extrapolate(samples: &[i64], target: usize) -> i64 {
+    let n = samples.len();
+    let (a, b, c) = (samples[n - 3], samples[n - 2], samples[n - 1]);
+    let m = (target - (n - 1)) as i64;
+    c + m * (c - b) + (c - 2 * b + a) * m * (m + 1) / 2
+}
+
+fn eval_steps(file: &str, steps: usize) -> usize {
+    let garden: Garden = file.into();
+    garden.reach(steps, false).plots(steps)
+}
+
+fn eval_steps_infinite(file: &str, steps: usize) -> Result<usize, String> {
+    let garden: Garden = file.into();
+    garden.reachable_infinite(steps, MAX_PERIODS)
+}
+
+pub(crate) fn eval_file(file: &str) -> usize {
+    eval_steps(file, NBR_STEPS)
+}
+
+pub(crate) fn eval_file_2(file: &str) -> Result<usize, String> {
+    eval_steps_infinite(file, NBR_STEPS_INFINITE)
+}
+pub(crate) fn print_sol_1(file: &str) {
+    print!("res : {}", eval_file(file));
+}
+pub(crate) fn print_sol_2(file: &str) {
+    match eval_file_2(file) {
+        Ok(res) => print!("res : {}", res),
+        Err(e) => print!("{}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{eval_steps, eval_steps_infinite, extrapolate, Garden};
+
+    fn data() -> &'static str {
+        r#"
+...........
+.....###.#.
+.###.##..#.
+..#.#...#..
+....#.#....
+.##..S####.
+.##..#...#.
+.......##..
+.##.#.####.
+.##..##.##.
+..........."#
+    }
+    #[test]
+    fn test_0() {
+        assert_eq!(16, eval_steps(data(), 6));
+        assert_eq!(42, eval_steps(data(), 100));
+    }
+    #[test]
+    fn test_1() {
+        assert_eq!(Ok(16), eval_steps_infinite(data(), 6));
+        assert_eq!(Ok(50), eval_steps_infinite(data(), 10));
+        assert_eq!(Ok(1594), eval_steps_infinite(data(), 50));
+        assert_eq!(Ok(6536), eval_steps_infinite(data(), 100));
+    }
+    #[test]
+    fn test_2() {
+        assert_eq!(Ok(167004), eval_steps_infinite(data(), 500));
+        assert_eq!(Ok(668697), eval_steps_infinite(data(), 1000));
+        assert_eq!(Ok(16733044), eval_steps_infinite(data(), 5000));
+    }
+    #[test]
+    fn test_3() {
+        // Direct BFS and extrapolation agree past the sampled periods.
+        let garden: Garden = data().into();
+        let reach = garden.reach(5 + 30 * 11, true);
+        let samples = (0..=12)
+            .map(|k| reach.plots(5 + k * 11) as i64)
+            .collect::<Vec<_>>();
+        assert_eq!(reach.plots(5 + 30 * 11) as i64, extrapolate(&samples, 30));
+    }
+    #[test]
+    fn test_4() {
+        // Walled in, the start is only reached on even steps, which the odd
+        // period keeps alternating.
+        assert!(eval_steps_infinite(".##\n#S#\n###", 5000).is_err());
+    }
+}
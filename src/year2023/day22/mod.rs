@@ -0,0 +1,257 @@
+use std::{collections::VecDeque, fmt::Display};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Brick {
+    /// Lowest corner, every coordinate being smaller than the ones of `end`.
+    pub(crate) start: (usize, usize, usize),
+    pub(crate) end: (usize, usize, usize),
+}
+
+impl From<&str> for Brick {
+    fn from(value: &str) -> Self {
+        let (start, end) = value.split_once('~').expect("Should have two ends");
+        let parse = |corner: &str| {
+            let mut coords = corner
+                .split(',')
+                .map(|c| c.trim().parse::<usize>().expect("Should be nbr"));
+            let mut next = || coords.next().expect("Should have 3 coordinates");
+            (next(), next(), next())
+        };
+        let (a, b) = (parse(start), parse(end));
+        Self {
+            start: (a.0.min(b.0), a.1.min(b.1), a.2.min(b.2)),
+            end: (a.0.max(b.0), a.1.max(b.1), a.2.max(b.2)),
+        }
+    }
+}
+
+impl Display for Brick {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (a, b) = (self.start, self.end);
+        write!(f, "{},{},{}~{},{},{}", a.0, a.1, a.2, b.0, b.1, b.2)
+    }
+}
+
+impl Brick {
+    /// Cells `(x, y)` covered by the brick seen from above.
+    fn footprint(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (self.start.0..=self.end.0)
+            .flat_map(move |x| (self.start.1..=self.end.1).map(move |y| (x, y)))
+    }
+
+    fn height(&self) -> usize {
+        self.end.2 - self.start.2 + 1
+    }
+}
+
+/// Bricks once settled, sorted by their lowest point, with the support graph.
+struct Stack {
+    pub(crate) bricks: Vec<Brick>,
+    /// `supported_by[i]` being the bricks right under brick `i`.
+    pub(crate) supported_by: Vec<Vec<usize>>,
+    /// `supports[i]` being the bricks right on top of brick `i`.
+    pub(crate) supports: Vec<Vec<usize>>,
+}
+
+impl From<&str> for Stack {
+    fn from(value: &str) -> Self {
+        let mut bricks = value
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| l.into())
+            .collect::<Vec<Brick>>();
+        bricks.sort_by_key(|b| b.start.2);
+        let width = bricks.iter().map(|b| b.end.0).max().unwrap_or(0) + 1;
+        let depth = bricks.iter().map(|b| b.end.1).max().unwrap_or(0) + 1;
+
+        // Highest settled level of every column, and the brick lying there.
+        let mut height_map: Vec<(usize, Option<usize>)> = vec![(0, None); width * depth];
+        let mut supported_by = vec![vec![]; bricks.len()];
+        let mut supports = vec![vec![]; bricks.len()];
+        for i in 0..bricks.len() {
+            let top = bricks[i]
+                .footprint()
+                .map(|(x, y)| height_map[x * depth + y].0)
+                .max()
+                .unwrap_or(0);
+            let mut under = bricks[i]
+                .footprint()
+                .filter_map(|(x, y)| match height_map[x * depth + y] {
+                    (z, Some(j)) if z == top => Some(j),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            under.sort_unstable();
+            under.dedup();
+            under.iter().for_each(|j| supports[*j].push(i));
+            supported_by[i] = under;
+
+            let height = bricks[i].height();
+            bricks[i].start.2 = top + 1;
+            bricks[i].end.2 = top + height;
+            let end = bricks[i].end.2;
+            bricks[i]
+                .footprint()
+                .for_each(|(x, y)| height_map[x * depth + y] = (end, Some(i)));
+        }
+        Self {
+            bricks,
+            supported_by,
+            supports,
+        }
+    }
+}
+
+impl Stack {
+    /// Bricks that can be removed without any other brick falling.
+    fn count_disintegrable(&self) -> usize {
+        self.supports
+            .iter()
+            .filter(|above| above.iter().all(|j| self.supported_by[*j].len() > 1))
+            .count()
+    }
+
+    /// Other bricks falling when `brick` gets disintegrated.
+    fn count_falling(&self, brick: usize) -> usize {
+        let mut remaining_supports = self
+            .supported_by
+            .iter()
+            .map(|s| s.len())
+            .collect::<Vec<_>>();
+        let mut queue = VecDeque::from([brick]);
+        let mut fallen = 0;
+        while let Some(b) = queue.pop_front() {
+            for above in self.supports[b].iter() {
+                remaining_supports[*above] -= 1;
+                if remaining_supports[*above] == 0 {
+                    fallen += 1;
+                    queue.push_back(*above);
+                }
+            }
+        }
+        fallen
+    }
+
+    fn label(idx: usize) -> char {
+        (b'A' + (idx % 26) as u8) as char
+    }
+
+    /// Side view of the stack as in the statement, looking along `y` when
+    /// `along_y` and along `x` otherwise.
+    fn view(&self, along_y: bool) -> String {
+        let coord = |c: (usize, usize, usize)| if along_y { c.0 } else { c.1 };
+        let width = self.bricks.iter().map(|b| coord(b.end)).max().unwrap_or(0) + 1;
+        let height = self.bricks.iter().map(|b| b.end.2).max().unwrap_or(0);
+        let mut lines = vec![];
+        for z in (1..=height).rev() {
+            let line = (0..width)
+                .map(|h| {
+                    let mut bricks = self.bricks.iter().enumerate().filter(|(_, b)| {
+                        coord(b.start) <= h && h <= coord(b.end) && b.start.2 <= z && z <= b.end.2
+                    });
+                    match (bricks.next(), bricks.next()) {
+                        (None, _) => '.',
+                        (Some((i, _)), None) => Self::label(i),
+                        _ => '?',
+                    }
+                })
+                .collect::<String>();
+            lines.push(format!("{} {}", line, z));
+        }
+        lines.push(format!("{} 0", "-".repeat(width)));
+        lines.join("\n")
+    }
+
+    /// Every settled brick with its neighbours, then the side views.
+    fn dump(&self) -> String {
+        let names = |bricks: &[usize]| {
+            bricks
+                .iter()
+                .map(|i| format!("{}{}", Self::label(*i), i))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let mut ret = self
+            .bricks
+            .iter()
+            .enumerate()
+            .map(|(i, b)| {
+                format!(
+                    "{}{} : {} // on [{}] // under [{}]",
+                    Self::label(i),
+                    i,
+                    b,
+                    names(&self.supported_by[i]),
+                    names(&self.supports[i])
+                )
+            })
+            .collect::<Vec<_>>();
+        ret.push(String::from(" x"));
+        ret.push(self.view(true));
+        ret.push(String::from(" y"));
+        ret.push(self.view(false));
+        ret.join("\n")
+    }
+}
+
+pub(crate) fn eval_file(file: &str) -> usize {
+    let stack: Stack = file.into();
+    stack.count_disintegrable()
+}
+
+pub(crate) fn eval_file_2(file: &str) -> usize {
+    let stack: Stack = file.into();
+    (0..stack.bricks.len())
+        .map(|i| stack.count_falling(i))
+        .sum()
+}
+pub(crate) fn print_sol_1(file: &str) {
+    print!("res : {}", eval_file(file));
+}
+pub(crate) fn print_sol_2(file: &str) {
+    print!("res : {}", eval_file_2(file));
+}
+pub(crate) fn print_dump(file: &str) {
+    let stack: Stack = file.into();
+    print!("{}", stack.dump());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{eval_file, eval_file_2, Stack};
+
+    fn data() -> &'static str {
+        r#"
+1,0,1~1,2,1
+0,0,2~2,0,2
+0,2,3~2,2,3
+0,0,4~0,2,4
+2,0,5~2,2,5
+0,1,6~2,1,6
+1,1,8~1,1,9"#
+    }
+    #[test]
+    fn test_0() {
+        assert_eq!(5, eval_file(data()));
+    }
+    #[test]
+    fn test_1() {
+        assert_eq!(7, eval_file_2(data()));
+    }
+    #[test]
+    fn test_2() {
+        let stack: Stack = data().into();
+        assert_eq!(
+            vec![1, 2, 2, 3, 3, 4, 5],
+            stack.bricks.iter().map(|b| b.start.2).collect::<Vec<_>>()
+        );
+        assert_eq!(vec![vec![1, 2], vec![3, 4]], stack.supports[..2].to_vec());
+        assert_eq!(
+            ".G. 6\n.G. 5\nFFF 4\nD.E 3\n??? 2\n.A. 1\n--- 0",
+            stack.view(true)
+        );
+        assert!(stack
+            .dump()
+            .starts_with("A0 : 1,0,1~1,2,1 // on [] // under [B1, C2]\n"));
+    }
+}
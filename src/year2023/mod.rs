@@ -17,3 +17,6 @@ pub(crate) mod day16;
 pub(crate) mod day17;
 pub(crate) mod day18;
 pub(crate) mod day19;
+pub(crate) mod day20;
+pub(crate) mod day21;
+pub(crate) mod day22;